@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Overall numbers of an analyzed game, as shown in the game list.
+ */
+export type AccuracySummary = { white_accuracy: number | null, black_accuracy: number | null, white_acpl: number | null, black_acpl: number | null, };
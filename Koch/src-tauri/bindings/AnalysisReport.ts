@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GameAnalysis } from "./GameAnalysis";
+
+export type AnalysisReport = { game_id: number, analysis: GameAnalysis | null, cancelled: boolean, error: string | null, };
@@ -1,7 +1,9 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AnalyzerStatus } from "./AnalyzerStatus";
 import type { Board } from "./Board";
 import type { LocalChat } from "./LocalChat";
 import type { PvObject } from "./PvObject";
+import type { Threat } from "./Threat";
 import type { UndoInfo } from "./UndoInfo";
 
-export type AnalyzerController = { game_id: number, board: Board, current_ply: number, board_undo: Array<UndoInfo>, last_threat: string | null, last_pv: PvObject | null, chat_history: LocalChat, };
+export type AnalyzerController = { game_id: number, board: Board, current_ply: number, current_node: number, board_undo: Array<UndoInfo>, last_threat: Threat | null, last_pv: PvObject | null, status: AnalyzerStatus, chat_history: LocalChat, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How long the analyzer searches a position.
+ */
+export type AnalyzerLimit = "Infinite" | { "Depth": number } | { "MoveTime": number } | { "Nodes": bigint } | { "Mate": number };
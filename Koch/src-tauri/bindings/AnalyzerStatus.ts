@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AnalyzerLimit } from "./AnalyzerLimit";
+import type { SearchState } from "./SearchState";
+
+/**
+ * What the analyzer engine is doing, emitted as `analyzer_status`.
+ */
+export type AnalyzerStatus = { state: SearchState, limit: AnalyzerLimit, depth: number, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A user-defined group of games, e.g. "Rook endgames".
+ */
+export type Collection = { collection_id: number, name: string, description: string | null, game_count: number, };
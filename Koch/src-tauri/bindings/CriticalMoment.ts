@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CriticalReason } from "./CriticalReason";
+import type { PieceColor } from "./PieceColor";
+
+/**
+ * A turning point of an analyzed game.
+ */
+export type CriticalMoment = { ply: number, played: string, san: string, color: PieceColor, best_move: string | null, fen: string, reason: CriticalReason | null, win_before: number, win_after: number, };
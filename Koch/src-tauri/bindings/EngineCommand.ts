@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EngineConfig } from "./EngineConfig";
 
-export type EngineCommand = { "SetFen": string } | "GoInfinite" | "Stop" | "Quit" | { "SetAndGo": [string, number] } | { "SetMultiPv": number } | { "SetHashSize": number } | { "SetThreads": number } | { "GetThreat": [string, number] };
+export type EngineCommand = { "SetFen": string } | "GoInfinite" | { "GoDepth": number } | { "GoMoveTime": number } | { "GoNodes": bigint } | { "GoMate": number } | "Stop" | "Quit" | { "SetAndGo": { position: string, fen: string, multiplier: number, min_depth: number | null, } } | { "SetMultiPv": number } | { "SetHashSize": number } | { "SetThreads": number } | { "SetEngine": EngineConfig } | { "SetUciOption": [string, string | null] } | { "GetThreat": string };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { UciOption } from "./UciOption";
+
+/**
+ * A registered UCI engine with the options it declared and the values set
+ * for them.
+ */
+export type EngineConfig = { engine_id: number, name: string, path: string, args: Array<string>, options: Array<UciOption>, values: { [key in string]?: string }, };
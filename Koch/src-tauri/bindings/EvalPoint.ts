@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EvalKind } from "./EvalKind";
+import type { MoveClassification } from "./MoveClassification";
+
+/**
+ * Evaluation after one main-line move, for the eval chart.
+ */
+export type EvalPoint = { ply: number, played: string, san: string, eval_kind: EvalKind | null, eval_value: number | null, best_move: string | null, classification: MoveClassification | null, };
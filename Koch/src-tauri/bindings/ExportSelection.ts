@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GameFilter } from "./GameFilter";
+
+/**
+ * Which stored games an export covers.
+ */
+export type ExportSelection = "All" | { "Ids": Array<number> } | { "Filter": GameFilter } | { "Collection": number };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GameAccuracy } from "./GameAccuracy";
+import type { PlyAnalysis } from "./PlyAnalysis";
+import type { SearchLimit } from "./SearchLimit";
+
+export type GameAnalysis = { game_id: number, engine: string, limit: SearchLimit, plies: Array<PlyAnalysis>, accuracy: GameAccuracy, analyzed_at: string, };
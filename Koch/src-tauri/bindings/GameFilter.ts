@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GameControllerMode } from "./GameControllerMode";
+import type { GameResult } from "./GameResult";
+import type { PieceColor } from "./PieceColor";
+
+/**
+ * Search filter over stored games. Every field is optional and the set
+ * fields are combined with AND.
+ */
+export type GameFilter = { player: string | null, color: PieceColor | null, result: GameResult | null, opening: string | null, eco: string | null, date_from: string | null, date_to: string | null, min_elo: number | null, max_elo: number | null, time_class: GameControllerMode | null, time_control: string | null, site: string | null, collection_id: number | null, tag: string | null, favorite: boolean | null, };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AccuracySummary } from "./AccuracySummary";
+import type { BoardMetaData } from "./BoardMetaData";
+
+export type GameListPage = { ids: Array<number>, games: Array<BoardMetaData>, accuracy: Array<AccuracySummary | null>, total: number, };
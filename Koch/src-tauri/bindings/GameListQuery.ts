@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GameFilter } from "./GameFilter";
+import type { GameSortField } from "./GameSortField";
+
+export type GameListQuery = { filter: GameFilter, sort_by: GameSortField, descending: boolean, limit: number, offset: number, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type GameSortField = "Id" | "Date" | "White" | "Black" | "Result" | "Opening" | "Eco" | "AverageElo" | "WhiteAccuracy" | "BlackAccuracy" | "WhiteAcpl" | "BlackAcpl";
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ScoreLine } from "./ScoreLine";
+
+/**
+ * Score for one opening, time class, rating band or termination.
+ */
+export type GroupScore = { key: string, name: string | null, score: ScoreLine, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Running totals of an import, so every importer reports the same numbers.
+ */
+export type ImportCounts = { new: number, duplicates: number, updated: number, failed: number, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ImportError } from "./ImportError";
+
+export type ImportReport = { processed: number, imported: number, duplicates: number, updated: number, failed: number, errors: Array<ImportError>, cancelled: boolean, error: string | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ImportCounts } from "./ImportCounts";
+
+/**
+ * Games imported from one monthly archive.
+ */
+export type MonthSync = { month: string, counts: ImportCounts, };
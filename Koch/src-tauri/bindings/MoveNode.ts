@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MoveStruct } from "./MoveStruct";
+
+/**
+ * One position in a game tree.
+ * The root node has no move and stands for the starting position; every other
+ * node holds the move that led to it. `children[0]` is the main continuation,
+ * the remaining children are variations in the order they were added.
+ */
+export type MoveNode = { id: number, parent: number | null, children: Array<number>, mv: MoveStruct | null, fen: string, pre_comments: Array<string>, comments: Array<string>, nags: Array<number>, };
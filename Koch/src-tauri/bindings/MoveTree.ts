@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MoveNode } from "./MoveNode";
+
+export type MoveTree = { nodes: { [key in number]?: MoveNode }, next_id: number, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ScoreLine } from "./ScoreLine";
+
+/**
+ * Results of one calendar month.
+ */
+export type PeriodStats = { period: string, score: ScoreLine, average_elo: number | null, average_opponent_elo: number | null, performance: number | null, };
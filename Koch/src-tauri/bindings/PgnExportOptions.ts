@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Which optional parts of a game end up in exported movetext.
+ */
+export type PgnExportOptions = { include_annotations: boolean, include_clocks: boolean, include_evals: boolean, };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GamePhase } from "./GamePhase";
+import type { SideAccuracy } from "./SideAccuracy";
+
+export type PhaseAccuracy = { phase: GamePhase, white: SideAccuracy | null, black: SideAccuracy | null, };
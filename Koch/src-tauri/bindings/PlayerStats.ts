@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GroupScore } from "./GroupScore";
+import type { PeriodStats } from "./PeriodStats";
+import type { ScoreLine } from "./ScoreLine";
+import type { Streaks } from "./Streaks";
+
+export type PlayerStats = { player: string, overall: ScoreLine, as_white: ScoreLine, as_black: ScoreLine, by_eco: Array<GroupScore>, by_time_class: Array<GroupScore>, by_opponent_rating: Array<GroupScore>, by_termination: Array<GroupScore>, streaks: Streaks, average_moves: number, unfinished: number, over_time: Array<PeriodStats>, };
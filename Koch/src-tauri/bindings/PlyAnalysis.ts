@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EvalKind } from "./EvalKind";
+import type { GamePhase } from "./GamePhase";
+import type { MoveClassification } from "./MoveClassification";
+import type { PieceColor } from "./PieceColor";
+
+/**
+ * Engine verdict on one main-line move.
+ */
+export type PlyAnalysis = { ply: number, played: string, san: string, color: PieceColor, phase: GamePhase, best_move: string | null, eval_kind: EvalKind, eval_value: number, cp_loss: number, accuracy: number, only_move: boolean, classification: MoveClassification, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BoardMetaData } from "./BoardMetaData";
+
+export type PositionMatch = { game_id: number, ply: number, meta_data: BoardMetaData, };
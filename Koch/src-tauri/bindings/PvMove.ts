@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PieceColor } from "./PieceColor";
+
+/**
+ * One move of a PV line, with the position it leads to.
+ */
+export type PvMove = { uci: string, san: string, move_number: number, color: PieceColor, fen: string, };
@@ -1,4 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { PvLineData } from "./PvLineData";
+import type { SanLine } from "./SanLine";
+import type { UciInfo } from "./UciInfo";
 
-export type PvObject = { fen: string, depth: number, lines: { [key in number]?: PvLineData }, };
+export type PvObject = { fen: string, depth: number, lines: { [key in number]?: PvLineData }, san_lines: { [key in number]?: SanLine }, line_info: { [key in number]?: UciInfo }, search_info: UciInfo | null, };
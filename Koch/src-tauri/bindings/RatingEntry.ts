@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GameControllerMode } from "./GameControllerMode";
+
+/**
+ * One rated game, for charting the rating of a time class.
+ */
+export type RatingEntry = { entry_id: number, time_class: GameControllerMode, game_id: number | null, rating_before: number, rating_after: number, opponent_rating: number, score: number, recorded_at: string, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PvMove } from "./PvMove";
+
+/**
+ * A PV line in SAN, e.g. "12...Nc6 13. Bb5 a6".
+ */
+export type SanLine = { text: string, moves: Array<PvMove>, };
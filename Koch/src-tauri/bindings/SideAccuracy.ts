@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Accuracy and average centipawn loss of one side over some moves.
+ */
+export type SideAccuracy = { accuracy: number, acpl: number, moves: number, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Longest and current runs of results, oldest game first.
+ */
+export type Streaks = { longest_win: number, longest_loss: number, longest_unbeaten: number, current_win: number, current_loss: number, current_unbeaten: number, };
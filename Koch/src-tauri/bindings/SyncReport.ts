@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ImportCounts } from "./ImportCounts";
+import type { MonthSync } from "./MonthSync";
+
+export type SyncReport = { months: Array<MonthSync>, total: ImportCounts, error: string | null, };
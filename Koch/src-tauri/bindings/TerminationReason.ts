@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type TerminationReason = "Checkmate" | "StaleMate" | "Draw" | "Timeout" | "Resignation";
+export type TerminationReason = "Checkmate" | "StaleMate" | "Draw" | "Timeout" | "Resignation" | "Unknown";
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PvLineData } from "./PvLineData";
+import type { SanLine } from "./SanLine";
+
+/**
+ * What the side that just moved threatens: the line it would play if it
+ * could move again.
+ */
+export type Threat = { fen: string, in_check: boolean, depth: number, line: PvLineData | null, san: SanLine, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EvalKind } from "./EvalKind";
+import type { Wdl } from "./Wdl";
+
+/**
+ * Everything an `info` line reports. Fields the engine left out are None.
+ */
+export type UciInfo = { multipv: number, depth: number | null, seldepth: number | null, nodes: bigint | null, nps: bigint | null, time: bigint | null, hashfull: number | null, tbhits: bigint | null, currmove: string | null, currmovenumber: number | null, eval_kind: EvalKind | null, eval_value: number | null, wdl: Wdl | null, lowerbound: boolean, upperbound: boolean, pv: string | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { UciOptionKind } from "./UciOptionKind";
+
+/**
+ * An `option name ... type ...` declaration of the `uci` handshake.
+ */
+export type UciOption = { name: string, kind: UciOptionKind, };
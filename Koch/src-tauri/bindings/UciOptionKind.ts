@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Value type of a UCI option, with its default and bounds.
+ */
+export type UciOptionKind = { "Spin": { default: bigint, min: bigint, max: bigint, } } | { "Check": { default: boolean, } } | { "Combo": { default: string, vars: Array<string>, } } | { "String": { default: string, } } | "Button";
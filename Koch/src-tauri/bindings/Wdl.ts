@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Win, draw and loss chances in permille, sent with `UCI_ShowWDL`.
+ */
+export type Wdl = { win: number, draw: number, loss: number, };
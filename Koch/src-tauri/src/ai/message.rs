@@ -56,7 +56,7 @@ pub async fn send_llm_request(
             translate_fen_for_model(&state.analyzer_controller.get_fen()),
             pv_data,
             pv_best_move,
-            if state.analyzer_controller.last_threat.is_some() {state.analyzer_controller.last_threat.clone().unwrap() } else {"No Threat".into()},
+            state.analyzer_controller.last_threat.as_ref().map(|threat| threat.to_string()).unwrap_or("No Threat".into()),
             msg
 
         );
@@ -77,6 +77,7 @@ pub async fn send_llm_request(
     let koch_sent_at = Utc::now().to_rfc3339();
     let _update_chat = {
         let mut state = state.lock().unwrap();
+        let database = state.database.clone();
         let current_chat = &mut state.analyzer_controller.chat_history;
         current_chat.chat_messages.extend(vec![
             LocalMessage {
@@ -92,7 +93,11 @@ pub async fn send_llm_request(
                 sent_at: koch_sent_at,
             },
         ]);
-        match current_chat.save() {
+        let saved = database
+            .connection()
+            .map_err(|e| e.to_string())
+            .and_then(|con| current_chat.save(&con));
+        match saved {
             Ok(_) => println!("chat saved"),
             Err(_) => println!("Failed to save chat"),
         };
@@ -0,0 +1,119 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::analyzer::game_analysis::PlyAnalysis;
+use crate::engine::board::GamePhase;
+use crate::engine::PieceColor;
+
+/// Winning chances of White, 0 - 100, for an eval in centipawns. Same
+/// logistic curve as lichess, fitted on rated games.
+pub fn win_percent(cp: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * cp as f64).exp()) - 1.0)
+}
+
+/// Accuracy (0 - 100) of a move that took the mover's winning chances from
+/// `win_before` to `win_after`.
+pub fn move_accuracy(win_before: f64, win_after: f64) -> f64 {
+    let drop = (win_before - win_after).max(0.0);
+    // +1 absorbs engine noise so that a best move is always 100
+    (103.1668 * (-0.04354 * drop).exp() - 3.1669 + 1.0).clamp(0.0, 100.0)
+}
+
+/// Accuracy and average centipawn loss of one side over some moves.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct SideAccuracy {
+    pub accuracy: f32,
+    pub acpl: f32,
+    pub moves: u32,
+}
+
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct PhaseAccuracy {
+    pub phase: GamePhase,
+    // None when the side made no move in this phase
+    pub white: Option<SideAccuracy>,
+    pub black: Option<SideAccuracy>,
+}
+
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct GameAccuracy {
+    pub white: Option<SideAccuracy>,
+    pub black: Option<SideAccuracy>,
+    // phases the game went through, in order
+    pub phases: Vec<PhaseAccuracy>,
+}
+
+// Average of the arithmetic and harmonic mean: the harmonic mean drags a
+// game with a few blunders down more than a plain average would.
+fn side_accuracy<'a>(plies: impl Iterator<Item = &'a PlyAnalysis>) -> Option<SideAccuracy> {
+    let (mut count, mut sum, mut inverse_sum, mut loss) = (0u32, 0.0, 0.0, 0u32);
+    for ply in plies {
+        count += 1;
+        sum += ply.accuracy as f64;
+        inverse_sum += 1.0 / (ply.accuracy as f64).max(1.0);
+        loss += ply.cp_loss;
+    }
+    if count == 0 {
+        return None;
+    }
+    let mean = sum / count as f64;
+    let harmonic = count as f64 / inverse_sum;
+    Some(SideAccuracy {
+        accuracy: ((mean + harmonic) / 2.0) as f32,
+        acpl: loss as f32 / count as f32,
+        moves: count,
+    })
+}
+
+pub fn game_accuracy(plies: &[PlyAnalysis]) -> GameAccuracy {
+    let of_side = |color: PieceColor, phase: Option<GamePhase>| {
+        side_accuracy(
+            plies
+                .iter()
+                .filter(|ply| ply.color == color && phase.is_none_or(|phase| ply.phase == phase)),
+        )
+    };
+    let mut phases: Vec<GamePhase> = Vec::new();
+    for ply in plies {
+        if !phases.contains(&ply.phase) {
+            phases.push(ply.phase);
+        }
+    }
+    GameAccuracy {
+        white: of_side(PieceColor::White, None),
+        black: of_side(PieceColor::Black, None),
+        phases: phases
+            .into_iter()
+            .map(|phase| PhaseAccuracy {
+                phase,
+                white: of_side(PieceColor::White, Some(phase)),
+                black: of_side(PieceColor::Black, Some(phase)),
+            })
+            .collect(),
+    }
+}
+
+/// Keep the overall numbers on the game row so the list can sort by them.
+pub fn store_accuracy(
+    con: &Connection,
+    game_id: u32,
+    accuracy: &GameAccuracy,
+) -> rusqlite::Result<()> {
+    con.execute(
+        "UPDATE games SET white_accuracy = ?1, black_accuracy = ?2, white_acpl = ?3,
+            black_acpl = ?4
+         WHERE game_id = ?5",
+        params![
+            accuracy.white.as_ref().map(|side| side.accuracy),
+            accuracy.black.as_ref().map(|side| side.accuracy),
+            accuracy.white.as_ref().map(|side| side.acpl),
+            accuracy.black.as_ref().map(|side| side.acpl),
+            game_id
+        ],
+    )?;
+    Ok(())
+}
@@ -13,14 +13,34 @@ use std::{
 #[ts(export)]
 pub enum EngineCommand {
     SetFen(String),
+    // search the current position with a new limit, which the following
+    // `SetAndGo` keep using
     GoInfinite,
+    GoDepth(u32),
+    // milliseconds
+    GoMoveTime(u32),
+    GoNodes(u64),
+    // mate in that many moves
+    GoMate(u32),
     Stop,
     Quit,
-    SetAndGo(String, i32), // <- CHANGED: add i32 multiplier
+    // `position` is the UCI position command, `fen` the resulting position (cache key),
+    // `multiplier` normalizes evals to White; cached lines are shown and searched
+    // further unless they already reach `min_depth` (or the depth limit)
+    SetAndGo {
+        position: String,
+        fen: String,
+        multiplier: i32,
+        min_depth: Option<u32>,
+    },
     SetMultiPv(usize),
     SetHashSize(usize),
     SetThreads(usize),
-    GetThreat(String, i32),
+    // switch to another engine, keeping the position
+    SetEngine(EngineConfig),
+    // any option the engine declared, None presses a button
+    SetUciOption(String, Option<String>),
+    GetThreat(String),
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, ts_rs::TS)]
@@ -31,18 +51,115 @@ pub enum EngineOption {
     Hash,
 }
 use serde::{Deserialize, Serialize};
-use stockfish::Stockfish;
 use tauri::{AppHandle, Emitter, Manager};
 use ts_rs::TS;
 
+use crate::analyzer::threat::{spawn_threat_worker, Threat, ThreatRequest};
+use crate::analyzer::uci_engine::{parse_info, UciEngine};
+use crate::database::engines::{engine_for, EngineConfig, EngineRole};
+use crate::engine::fen::fen_parser;
 use crate::engine::Board;
+use crate::server::server::Settings;
 use crate::{
+    database::eval_cache::{lookup_eval, store_eval},
     engine::{
+        move_tree::ROOT_NODE,
         serializer::{serialize_analyzer_controller, SerializedAnalyzerController},
         ChessPiece, PieceColor, PieceType,
     },
-    server::server::{load_settings, EvalKind, PvLineData, PvObject, ServerState},
+    server::server::{EvalKind, PvLineData, PvObject, ServerState},
 };
+pub const ANALYZER_LIMIT_SETTING: &str = "AnalyzerLimit";
+// depth, milliseconds, nodes or moves to mate, depending on the limit
+pub const ANALYZER_LIMIT_VALUE_SETTING: &str = "AnalyzerLimitValue";
+
+/// How long the analyzer searches a position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub enum AnalyzerLimit {
+    #[default]
+    Infinite,
+    Depth(u32),
+    // milliseconds
+    MoveTime(u32),
+    Nodes(u64),
+    // mate in that many moves
+    Mate(u32),
+}
+
+impl AnalyzerLimit {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let value = settings
+            .map
+            .get(ANALYZER_LIMIT_VALUE_SETTING)
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .filter(|value| *value > 0);
+        let kind = settings
+            .map
+            .get(ANALYZER_LIMIT_SETTING)
+            .map(|kind| kind.trim());
+        match (kind, value) {
+            (Some("Depth"), Some(depth)) => AnalyzerLimit::Depth(depth as u32),
+            (Some("MoveTime"), Some(movetime)) => AnalyzerLimit::MoveTime(movetime as u32),
+            (Some("Nodes"), Some(nodes)) => AnalyzerLimit::Nodes(nodes),
+            (Some("Mate"), Some(moves)) => AnalyzerLimit::Mate(moves as u32),
+            _ => AnalyzerLimit::Infinite,
+        }
+    }
+
+    pub fn save(&self, settings: &mut Settings) {
+        let (kind, value) = match self {
+            AnalyzerLimit::Infinite => ("Infinite", 0),
+            AnalyzerLimit::Depth(depth) => ("Depth", *depth as u64),
+            AnalyzerLimit::MoveTime(movetime) => ("MoveTime", *movetime as u64),
+            AnalyzerLimit::Nodes(nodes) => ("Nodes", *nodes),
+            AnalyzerLimit::Mate(moves) => ("Mate", *moves as u64),
+        };
+        settings.update(ANALYZER_LIMIT_SETTING.to_string(), kind.to_string());
+        settings.update(ANALYZER_LIMIT_VALUE_SETTING.to_string(), value.to_string());
+    }
+
+    pub fn go_command(&self) -> String {
+        match self {
+            AnalyzerLimit::Infinite => "go infinite".to_string(),
+            AnalyzerLimit::Depth(depth) => format!("go depth {}", depth),
+            AnalyzerLimit::MoveTime(movetime) => format!("go movetime {}", movetime),
+            AnalyzerLimit::Nodes(nodes) => format!("go nodes {}", nodes),
+            AnalyzerLimit::Mate(moves) => format!("go mate {}", moves),
+        }
+    }
+
+    fn command(&self) -> EngineCommand {
+        match *self {
+            AnalyzerLimit::Infinite => EngineCommand::GoInfinite,
+            AnalyzerLimit::Depth(depth) => EngineCommand::GoDepth(depth),
+            AnalyzerLimit::MoveTime(movetime) => EngineCommand::GoMoveTime(movetime),
+            AnalyzerLimit::Nodes(nodes) => EngineCommand::GoNodes(nodes),
+            AnalyzerLimit::Mate(moves) => EngineCommand::GoMate(moves),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub enum SearchState {
+    #[default]
+    Idle,
+    Searching,
+    // the engine reached the limit, or had cached lines deep enough
+    Finished,
+}
+
+/// What the analyzer engine is doing, emitted as `analyzer_status`.
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct AnalyzerStatus {
+    pub state: SearchState,
+    pub limit: AnalyzerLimit,
+    // depth of the lines shown
+    pub depth: u32,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct BoardState {
@@ -252,9 +369,13 @@ pub struct AnalyzerController {
     pub game_id: usize,
     pub board: Board,
     pub current_ply: i32,
+    // node of `board.meta_data.move_tree` the board is at; `board_undo` holds one
+    // entry per move on the path from the root to it
+    pub current_node: usize,
     pub board_undo: Vec<UndoInfo>,
-    pub last_threat: Option<String>,
+    pub last_threat: Option<Threat>,
     pub last_pv: Option<PvObject>,
+    pub status: AnalyzerStatus,
     pub chat_history: LocalChat,
 }
 
@@ -265,9 +386,11 @@ impl Default for AnalyzerController {
             board: Board::default(),
             // start at -1 to represent the initial position (no moves applied)
             current_ply: -1,
+            current_node: ROOT_NODE,
             board_undo: Vec::new(),
             last_threat: None,
             last_pv: None,
+            status: AnalyzerStatus::default(),
             chat_history: LocalChat::default(),
         }
     }
@@ -304,57 +427,48 @@ pub fn start_analyzer_thread(
     let (pv_tx, pv_rx) = sync_channel::<PvObject>(8);
 
     thread::spawn(move || {
-        let stockfish_go_config = String::from("go depth 40 movetime 20000");
         let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
             println!("[Analyzer] Thread starting...");
-            let mut engine = match Stockfish::new("/usr/bin/stockfish") {
-                Ok(e) => e,
+            let (database, settings) = match app_handle.state::<Mutex<ServerState>>().lock() {
+                Ok(state) => (state.database.clone(), state.settings.clone()),
+                Err(e) => {
+                    eprintln!("[Analyzer] state unavailable: {e}");
+                    return;
+                }
+            };
+            let engine_config = database
+                .connection()
+                .map(|con| engine_for(&con, &settings, EngineRole::Analyzer))
+                .unwrap_or_else(|_| EngineConfig::default_engine());
+            let (mut engine, engine_id) = match engine_config.launch() {
+                Ok(engine) => engine,
                 Err(e) => {
                     eprintln!("[Analyzer] Failed to start engine: {e}");
                     return;
                 }
             };
 
+            // threats are searched next to the analysis, on a second engine
+            let threat_tx = spawn_threat_worker(app_handle.clone(), engine_config.clone());
+
+            let mut pv_cache = PvCache {
+                engine_id,
+                fen: String::new(),
+                stored_depth: 0,
+                multipv: apply_engine_settings(&mut engine, &settings),
+            };
+            println!("[Analyzer] Engine: {}", pv_cache.engine_id);
+
             let mut is_searching = false;
             let mut current_fen = String::new();
             let mut current_pv = PvObject::default();
             let mut color_multiplier: i32 = 1; // <- NEW: multiplier for eval (white perspective)
+            let mut limit = AnalyzerLimit::from_settings(&settings);
+            // position being searched, to write the lines in SAN
+            let mut san_board: Option<Board> = None;
 
             let start_time = Instant::now();
             let mut last_clock_tick = Instant::now();
-            match load_settings() {
-                Ok(mut settings) => {
-                    let pv = settings
-                        .map
-                        .entry("MultiPV".to_string())
-                        .or_insert("1".to_string());
-                    match engine.set_option("MultiPV", pv) {
-                        Ok(()) => {}
-                        Err(e) => eprintln!("{e}"),
-                    }
-
-                    let th = settings
-                        .map
-                        .entry("Threads".to_string())
-                        .or_insert("1".to_string());
-                    match engine.set_option("Threads", th) {
-                        Ok(()) => {}
-                        Err(e) => eprintln!("{e}"),
-                    }
-
-                    let hs = settings
-                        .map
-                        .entry("HashSize".to_string())
-                        .or_insert("128".to_string());
-                    match engine.set_option("HashSize", hs) {
-                        Ok(()) => {}
-                        Err(e) => eprintln!("{e}"),
-                    }
-                }
-                Err(e) => {
-                    eprint!("{e}");
-                }
-            }
 
             loop {
                 // Handle commands
@@ -367,27 +481,70 @@ pub fn start_analyzer_thread(
                                     let _ = engine.uci_send("stop");
                                     drain_until_bestmove(&mut engine);
                                 }
+                                pv_cache.flush(&app_handle, &current_pv);
+                                pv_cache.fen.clear();
                                 engine.ensure_ready().ok();
 
                                 match engine.set_fen_position(&fen) {
                                     Ok(_) => println!("[Analyzer] FEN set successfully: {}", &fen),
                                     Err(e) => eprintln!("[Analyzer] Failed to set FEN: {}", e),
                                 }
+                                san_board = scratch_board(&fen);
                                 current_fen = fen;
                                 current_pv = PvObject {
                                     fen: current_fen.clone(),
                                     depth: 0,
                                     lines: HashMap::new(),
+                                    san_lines: HashMap::new(),
+                                    line_info: HashMap::new(),
+                                    search_info: None,
                                 };
 
                                 let _ = app_handle.emit("pv_update", current_pv.clone());
+                                report_status(&app_handle, SearchState::Idle, limit, 0);
                             }
-                            EngineCommand::GoInfinite => {
+                            EngineCommand::GoInfinite
+                            | EngineCommand::GoDepth(_)
+                            | EngineCommand::GoMoveTime(_)
+                            | EngineCommand::GoNodes(_)
+                            | EngineCommand::GoMate(_) => {
+                                limit = match command {
+                                    EngineCommand::GoDepth(depth) => AnalyzerLimit::Depth(depth),
+                                    EngineCommand::GoMoveTime(movetime) => {
+                                        AnalyzerLimit::MoveTime(movetime)
+                                    }
+                                    EngineCommand::GoNodes(nodes) => AnalyzerLimit::Nodes(nodes),
+                                    EngineCommand::GoMate(moves) => AnalyzerLimit::Mate(moves),
+                                    _ => AnalyzerLimit::Infinite,
+                                };
+                                if is_searching {
+                                    is_searching = false;
+                                    let _ = engine.uci_send("stop");
+                                    drain_until_bestmove(&mut engine);
+                                }
+                                pv_cache.flush(&app_handle, &current_pv);
+                                if current_fen.is_empty() {
+                                    report_status(&app_handle, SearchState::Idle, limit, 0);
+                                    continue;
+                                }
                                 engine.ensure_ready().ok();
-                                match engine.uci_send(&stockfish_go_config).ok() {
-                                    Some(_) => is_searching = true,
-                                    None => eprintln!("[Analyzer] fif not sbt stockfish go ocngic"),
+                                // SetFen leaves a bare FEN, SetAndGo a UCI position command
+                                let position = match current_fen.starts_with("position ") {
+                                    true => engine.uci_send(&current_fen),
+                                    false => engine.set_fen_position(&current_fen),
                                 };
+                                match position.and_then(|_| engine.uci_send(&limit.go_command())) {
+                                    Ok(_) => {
+                                        is_searching = true;
+                                        report_status(
+                                            &app_handle,
+                                            SearchState::Searching,
+                                            limit,
+                                            current_pv.depth,
+                                        );
+                                    }
+                                    Err(e) => eprintln!("[Analyzer] Failed to start search: {e}"),
+                                }
                             }
                             EngineCommand::Stop => {
                                 if is_searching {
@@ -395,73 +552,145 @@ pub fn start_analyzer_thread(
                                     let _ = engine.uci_send("stop");
                                     drain_until_bestmove(&mut engine);
                                 }
+                                pv_cache.flush(&app_handle, &current_pv);
+                                report_status(
+                                    &app_handle,
+                                    SearchState::Idle,
+                                    limit,
+                                    current_pv.depth,
+                                );
+                            }
+                            EngineCommand::Quit => {
+                                pv_cache.flush(&app_handle, &current_pv);
+                                break;
                             }
-                            EngineCommand::Quit => break,
 
-                            EngineCommand::SetAndGo(fen, mult) => {
+                            EngineCommand::SetAndGo {
+                                position,
+                                fen,
+                                multiplier,
+                                min_depth,
+                            } => {
                                 if is_searching {
                                     is_searching = false;
                                     let _ = engine.uci_send("stop");
                                     drain_until_bestmove(&mut engine);
                                 }
-                                engine.ensure_ready().ok();
-                                //position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1  moves e2e4 g7g6 d2d4 f8g7s
-                                match engine.uci_send(&fen) {
-                                    Ok(_) => println!("[Analyzer] FEn Set: {}", &fen),
-                                    Err(e) => eprintln!("{e}"),
-                                };
-                                current_fen = fen;
-                                color_multiplier = mult; // <- store multiplier for later
-                                current_pv = PvObject {
-                                    fen: current_fen.clone(),
-                                    depth: 0,
-                                    lines: HashMap::new(),
-                                };
-                                engine.ensure_ready().ok();
+                                pv_cache.flush(&app_handle, &current_pv);
+                                current_fen = position;
+                                color_multiplier = multiplier; // <- store multiplier for later
+                                san_board = scratch_board(&fen);
+                                pv_cache.fen = fen;
 
+                                let cached = pv_cache.lookup(&app_handle);
+                                let target_depth = min_depth.or(match limit {
+                                    AnalyzerLimit::Depth(depth) => Some(depth),
+                                    _ => None,
+                                });
+                                let deep_enough = cached.as_ref().is_some_and(|pv| {
+                                    target_depth.is_some_and(|target| pv.depth >= target)
+                                });
+                                current_pv = cached.unwrap_or_default();
+                                current_pv.fen = current_fen.clone();
+                                if let Some(board) = &san_board {
+                                    let indexes: Vec<u8> =
+                                        current_pv.lines.keys().copied().collect();
+                                    for index in indexes {
+                                        current_pv.update_san_line(board, index);
+                                    }
+                                }
+                                pv_cache.stored_depth = current_pv.depth;
+                                if let Ok(mut global_state) =
+                                    app_handle.state::<Mutex<ServerState>>().lock()
+                                {
+                                    global_state.analyzer_controller.last_pv =
+                                        Some(current_pv.clone());
+                                }
                                 let _ = app_handle.emit("pv_update", current_pv.clone());
 
-                                if let Err(e) = engine.uci_send(&stockfish_go_config) {
-                                    eprintln!("Failed to send go infinite: {e}");
+                                if deep_enough {
+                                    println!(
+                                        "[Analyzer] Using cached lines at depth {}",
+                                        current_pv.depth
+                                    );
+                                    report_status(
+                                        &app_handle,
+                                        SearchState::Finished,
+                                        limit,
+                                        current_pv.depth,
+                                    );
                                 } else {
-                                    is_searching = true;
+                                    engine.ensure_ready().ok();
+                                    //position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1  moves e2e4 g7g6 d2d4 f8g7s
+                                    match engine.uci_send(&current_fen) {
+                                        Ok(_) => println!("[Analyzer] FEn Set: {}", &current_fen),
+                                        Err(e) => eprintln!("{e}"),
+                                    };
+                                    engine.ensure_ready().ok();
+
+                                    if let Err(e) = engine.uci_send(&limit.go_command()) {
+                                        eprintln!("Failed to send go: {e}");
+                                    } else {
+                                        is_searching = true;
+                                        report_status(
+                                            &app_handle,
+                                            SearchState::Searching,
+                                            limit,
+                                            current_pv.depth,
+                                        );
+                                    }
                                 }
                             }
-                            EngineCommand::GetThreat(fen, _mult) => {
-                                let app_handle_clone = app_handle.clone();
-                                let flipped_fen = flip_fen_turn(&fen);
-
-                                // 1. Spawning a NEW thread for the transient engine
-                                thread::spawn(move || {
-                                    println!("[Analyzer] Starting background threat search...");
-
-                                    // 2. This engine is TOTALLY SEPARATE from the main one
-                                    if let Ok(mut temp_engine) =
-                                        Stockfish::new("/usr/bin/stockfish")
-                                    {
-                                        temp_engine.set_fen_position(&flipped_fen).ok();
-
-                                        // 3. This go_for now owns its own pipe exclusively
-                                        match temp_engine.go() {
-                                            Ok(out) => {
-                                                let threat_move = out.best_move();
-                                                println!("[Analyzer]THreat foun {}", threat_move);
-                                                // Update the global state and UI
-                                                if let Ok(mut global_state) = app_handle_clone
-                                                    .state::<Mutex<ServerState>>()
-                                                    .lock()
-                                                {
-                                                    global_state.analyzer_controller.last_threat =
-                                                        Some(threat_move.into());
-                                                }
-                                                let _ = app_handle_clone
-                                                    .emit("threat_update", threat_move);
-                                            }
-                                            Err(e) => eprintln!("[Threat Search] Error: {e}"),
-                                        }
-                                        // temp_engine goes out of scope and shuts down cleanly here
+                            EngineCommand::SetEngine(config) => {
+                                if is_searching {
+                                    is_searching = false;
+                                    let _ = engine.uci_send("stop");
+                                    drain_until_bestmove(&mut engine);
+                                }
+                                pv_cache.flush(&app_handle, &current_pv);
+                                let (new_engine, engine_id) = match config.launch() {
+                                    Ok(engine) => engine,
+                                    Err(e) => {
+                                        eprintln!("[Analyzer] Failed to start engine: {e}");
+                                        continue;
                                     }
-                                });
+                                };
+                                let settings = app_handle
+                                    .state::<Mutex<ServerState>>()
+                                    .lock()
+                                    .map(|state| state.settings.clone())
+                                    .unwrap_or_else(|_| settings.clone());
+                                engine = new_engine;
+                                threat_tx.send(ThreatRequest::SetEngine(config)).ok();
+                                pv_cache.engine_id = engine_id;
+                                pv_cache.multipv = apply_engine_settings(&mut engine, &settings);
+                                pv_cache.stored_depth = 0;
+                                current_pv.depth = 0;
+                                current_pv.lines.clear();
+                                current_pv.san_lines.clear();
+                                current_pv.line_info.clear();
+                                current_pv.search_info = None;
+                                println!("[Analyzer] Engine: {}", pv_cache.engine_id);
+                                let _ = app_handle.emit("pv_update", current_pv.clone());
+                                report_status(&app_handle, SearchState::Idle, limit, 0);
+                            }
+                            EngineCommand::SetUciOption(name, value) => {
+                                engine.ensure_ready().ok();
+                                let sent = match &value {
+                                    Some(value) => engine.set_option(&name, value),
+                                    None => engine.uci_send(&format!("setoption name {}", name)),
+                                };
+                                if let Err(e) = sent {
+                                    eprintln!("[Analyzer] Set {} failed: {}", name, e);
+                                }
+                            }
+                            EngineCommand::GetThreat(fen) => {
+                                threat_tx
+                                    .send(ThreatRequest::Search(fen))
+                                    .inspect_err(|e| {
+                                        eprintln!("[Analyzer] GetThreat send failed: {e}")
+                                    })
+                                    .ok();
                             }
                             EngineCommand::SetHashSize(hash) => {
                                 engine.ensure_ready().ok();
@@ -478,6 +707,7 @@ pub fn start_analyzer_thread(
                                 engine.ensure_ready().ok();
                                 match engine.set_option("MultiPV", &cnt.to_string()) {
                                     Ok(_) => {
+                                        pv_cache.multipv = cnt as u32;
                                         println!("[Analyzer] Set MultiPV to {} success", cnt)
                                     }
                                     Err(e) => {
@@ -520,57 +750,29 @@ pub fn start_analyzer_thread(
                     //let _ = writeln!(f_log, "{line}");
 
                     if line.starts_with("bestmove") {
-                        // Keep search alive; ignore spontaneous bestmove
-                        let _ = engine.uci_send(&stockfish_go_config);
+                        // the limit was reached, or an infinite search ran out of moves
+                        is_searching = false;
+                        pv_cache.flush(&app_handle, &current_pv);
+                        report_status(&app_handle, SearchState::Finished, limit, current_pv.depth);
                         continue;
                     }
 
-                    if line.contains(" multipv ") && line.contains(" pv ") {
-                        let parts: Vec<&str> = line.split_whitespace().collect();
-                        let mut multipv_idx: u8 = 1;
-                        let mut depth: u32 = 0;
-                        let mut moves = String::new();
-                        let mut score_value: i32 = 0;
-                        let mut score_kind = EvalKind::Centipawn;
-
-                        let mut i = 0;
-                        while i < parts.len() {
-                            match parts[i] {
-                                "multipv" if i + 1 < parts.len() => {
-                                    multipv_idx = parts[i + 1].parse().unwrap_or(1);
-                                    i += 1;
-                                }
-                                "depth" if i + 1 < parts.len() => {
-                                    depth = parts[i + 1].parse().unwrap_or(0);
-                                    i += 1;
-                                }
-                                "score" if i + 2 < parts.len() => {
-                                    score_kind = if parts[i + 1] == "mate" {
-                                        EvalKind::Mate
-                                    } else {
-                                        EvalKind::Centipawn
-                                    };
-                                    score_value = parts[i + 2].parse().unwrap_or(0);
-                                    i += 2;
-                                }
-                                "pv" => {
-                                    moves = parts[i + 1..].join(" ");
-                                    break;
-                                }
-                                _ => {}
-                            }
-                            i += 1;
-                        }
-
-                        if depth >= current_pv.depth {
+                    let Some(mut info) = parse_info(&line) else {
+                        continue;
+                    };
+                    // <- Apply normalization here
+                    info.normalize(color_multiplier);
+                    let depth = info.depth.unwrap_or(0);
+                    match info.line() {
+                        Some(line_data) if depth >= current_pv.depth => {
+                            let multipv_idx = info.multipv;
                             current_pv.depth = depth;
-                            let line_data = PvLineData {
-                                moves,
-                                eval_kind: score_kind,
-                                // <- Apply normalization here
-                                eval_value: score_value * color_multiplier,
-                            };
                             current_pv.lines.insert(multipv_idx, line_data);
+                            if let Some(board) = &san_board {
+                                current_pv.update_san_line(board, multipv_idx);
+                            }
+                            current_pv.search_info = Some(info.clone());
+                            current_pv.line_info.insert(multipv_idx, info);
                             if let Ok(mut global_state) =
                                 app_handle.state::<Mutex<ServerState>>().lock()
                             {
@@ -578,6 +780,13 @@ pub fn start_analyzer_thread(
                             }
                             let _ = app_handle.emit("pv_update", current_pv.clone());
                         }
+                        Some(_) => {}
+                        // progress without a line, e.g. `currmove`, `hashfull` or a
+                        // bound-only score
+                        None => {
+                            current_pv.search_info = Some(info);
+                            let _ = app_handle.emit("pv_update", current_pv.clone());
+                        }
                     }
                 } else {
                     thread::sleep(Duration::from_millis(10));
@@ -593,6 +802,34 @@ pub fn start_analyzer_thread(
 
     (cmd_tx, pv_rx)
 }
+// Board of `fen` with its move cache, None when the FEN isn't known or valid.
+pub(crate) fn scratch_board(fen: &str) -> Option<Board> {
+    if fen.is_empty() {
+        return None;
+    }
+    let mut board = fen_parser(&fen.to_string()).ok()?;
+    board.rerender_move_cache();
+    Some(board)
+}
+
+fn report_status(app_handle: &AppHandle, state: SearchState, limit: AnalyzerLimit, depth: u32) {
+    let status = AnalyzerStatus {
+        state,
+        limit,
+        depth,
+    };
+    if let Ok(mut global_state) = app_handle.state::<Mutex<ServerState>>().lock() {
+        global_state.analyzer_controller.status = status.clone();
+    }
+    let _ = app_handle.emit("analyzer_status", status);
+}
+
+/// Multipv index, depth and line of an `info ... pv ...` line with an exact
+/// score. The eval is from the side to move, as the engine reports it.
+pub fn parse_pv_info(line: &str) -> Option<(u8, u32, PvLineData)> {
+    let info = parse_info(line)?;
+    Some((info.multipv, info.depth.unwrap_or(0), info.line()?))
+}
 /*
 #[tauri::command]
 pub fn try_analyzer_move(
@@ -644,8 +881,15 @@ pub fn try_analyzer_move(
     None
 }
  */
+/// Analyze the position after main-line move `current_move` (-1 for the start).
+/// Cached lines are shown right away while the engine searches past them; the
+/// search is skipped when they already reach `min_depth` or the depth limit.
 #[tauri::command]
-pub fn set_analyzer_fen(state: tauri::State<'_, Mutex<ServerState>>, current_move: isize) -> bool {
+pub fn set_analyzer_fen(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    current_move: isize,
+    min_depth: Option<u32>,
+) -> bool {
     let state = state.lock().unwrap();
     println!("Called set fen for{}", current_move);
 
@@ -666,22 +910,28 @@ pub fn set_analyzer_fen(state: tauri::State<'_, Mutex<ServerState>>, current_mov
         true // default if malformed
     };
 
+    let mut position_fen = start_fen.clone();
     if current_move != -1 {
+        // The frontend addresses the main line by index; when the analyzer sits on
+        // that ply (possibly inside a variation) use its actual node instead.
+        let analyzer = &state.analyzer_controller;
+        let tree = &analyzer.board.meta_data.move_tree;
+        let node = if analyzer.current_ply as isize == current_move {
+            Some(analyzer.current_node)
+        } else {
+            tree.mainline().get(current_move as usize).copied()
+        };
+        position_fen = match node {
+            Some(node) if node == analyzer.current_node => analyzer.get_fen(),
+            Some(node) => tree.get(node).map(|n| n.fen.clone()).unwrap_or_default(),
+            None => String::new(),
+        };
         let mut moves = String::new();
-        for i in 0..=current_move {
-            match state
-                .analyzer_controller
-                .board
-                .meta_data
-                .move_list
-                .get(i as usize)
-            {
-                Some(mv) => {
-                    moves.push_str(&format!("{} ", mv.uci.clone()));
-                    // flip side to move for each half-move
-                    white_to_move = !white_to_move;
-                }
-                None => {}
+        for id in node.map(|node| tree.path_to(node)).unwrap_or_default() {
+            if let Some(mv) = tree.get(id).and_then(|n| n.mv.as_ref()) {
+                moves.push_str(&format!("{} ", mv.uci.clone()));
+                // flip side to move for each half-move
+                white_to_move = !white_to_move;
             }
         }
         if !moves.is_empty() {
@@ -706,7 +956,14 @@ pub fn set_analyzer_fen(state: tauri::State<'_, Mutex<ServerState>>, current_mov
         return false;
     };
 
-    if tx.send(EngineCommand::SetAndGo(fen, multiplier)).is_err() {
+    // without a FEN the position is analyzed but not cached
+    let command = EngineCommand::SetAndGo {
+        position: fen,
+        fen: position_fen,
+        multiplier,
+        min_depth,
+    };
+    if tx.send(command).is_err() {
         eprintln!("[Analyzer] SetAndGo send failed");
         return false;
     }
@@ -779,6 +1036,28 @@ pub fn set_engine_option(
         }
     }
 }
+/// Search the current position with `limit` and keep it, also as the
+/// default of the next sessions.
+#[tauri::command]
+pub fn set_analyzer_limit(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    limit: AnalyzerLimit,
+) -> bool {
+    let mut state = state.lock().unwrap();
+    limit.save(&mut state.settings);
+    if let Err(e) = state.settings.save() {
+        eprintln!("[Analyzer] Failed to save settings: {}", e);
+    }
+    let Some(tx) = &state.analyzer_tx else {
+        eprintln!("[Analyzer] tx missing");
+        return false;
+    };
+    if tx.send(limit.command()).is_err() {
+        eprintln!("[Analyzer] Go send failed");
+        return false;
+    }
+    true
+}
 #[tauri::command]
 pub fn get_analyzer_settings(
     state: tauri::State<'_, Mutex<ServerState>>,
@@ -807,15 +1086,91 @@ pub fn get_analyzer_settings(
     };
     Some((pvs, th, hs))
 }
-fn flip_fen_turn(fen: &str) -> String {
-    let mut parts: Vec<&str> = fen.split_whitespace().collect();
-    if parts.len() > 1 {
-        // Switch 'w' to 'b' or 'b' to 'w'
-        parts[1] = if parts[1] == "w" { "b" } else { "w" };
+// MultiPV, Threads and Hash from the settings; returns the number of lines.
+fn apply_engine_settings(engine: &mut UciEngine, settings: &Settings) -> u32 {
+    let setting = |key: &str, default: &str| {
+        settings
+            .map
+            .get(key)
+            .cloned()
+            .unwrap_or(default.to_string())
+    };
+    let multipv = setting("MultiPV", "1");
+    for (name, value) in [
+        ("MultiPV", multipv.clone()),
+        ("Threads", setting("Threads", "1")),
+        ("Hash", setting("HashSize", "128")),
+    ] {
+        if let Err(e) = engine.set_option(name, &value) {
+            eprintln!("[Analyzer] Set {} to {} failed: {}", name, value, e);
+        }
+    }
+    // win/draw/loss chances in the info lines
+    if engine.supports("UCI_ShowWDL") {
+        if let Err(e) = engine.set_option("UCI_ShowWDL", "true") {
+            eprintln!("[Analyzer] Set UCI_ShowWDL failed: {}", e);
+        }
     }
-    parts.join(" ")
+    multipv.parse().unwrap_or(1)
 }
-pub fn drain_until_bestmove(engine: &mut Stockfish) {
+
+// shallower lines, e.g. from scrolling past a move, are not worth caching
+const MIN_CACHED_DEPTH: u32 = 12;
+
+// Eval cache state of the analyzer thread: the position being analyzed and
+// the depth already stored for it.
+struct PvCache {
+    engine_id: String,
+    fen: String,
+    stored_depth: u32,
+    multipv: u32,
+}
+
+impl PvCache {
+    fn lookup(&self, app_handle: &AppHandle) -> Option<PvObject> {
+        if self.fen.is_empty() {
+            return None;
+        }
+        let database = app_handle
+            .state::<Mutex<ServerState>>()
+            .lock()
+            .ok()?
+            .database
+            .clone();
+        let con = database
+            .connection()
+            .inspect_err(|e| eprintln!("[Analyzer] eval cache unavailable: {e}"))
+            .ok()?;
+        lookup_eval(&con, &self.fen, &self.engine_id, self.multipv)
+            .inspect_err(|e| eprintln!("[Analyzer] eval cache lookup failed: {e}"))
+            .ok()
+            .flatten()
+    }
+
+    /// Store `pv` if it is deeper than what was stored for this position and
+    /// at least `MIN_CACHED_DEPTH` deep.
+    fn flush(&mut self, app_handle: &AppHandle, pv: &PvObject) {
+        if self.fen.is_empty() || pv.depth < MIN_CACHED_DEPTH || pv.depth <= self.stored_depth {
+            return;
+        }
+        let Ok(database) = app_handle
+            .state::<Mutex<ServerState>>()
+            .lock()
+            .map(|state| state.database.clone())
+        else {
+            return;
+        };
+        let stored = database
+            .connection()
+            .and_then(|con| store_eval(&con, &self.fen, &self.engine_id, self.multipv, pv));
+        match stored {
+            Ok(()) => self.stored_depth = pv.depth,
+            Err(e) => eprintln!("[Analyzer] could not cache eval: {e}"),
+        }
+    }
+}
+
+pub fn drain_until_bestmove(engine: &mut UciEngine) {
     loop {
         let l = engine.read_line();
         if l.starts_with("bestmove") {
@@ -828,14 +1183,13 @@ pub fn drain_until_bestmove(engine: &mut Stockfish) {
 pub fn get_threat(state: tauri::State<'_, Mutex<ServerState>>) {
     let mut state = state.lock().unwrap();
     let fen = state.analyzer_controller.board.to_string();
-    let multiplier: i32 = if fen.contains('w') { 1 } else { -1 };
 
     let Some(tx) = &state.analyzer_tx else {
         eprintln!("[Analyzer] tx missing");
         return;
     };
 
-    if tx.send(EngineCommand::GetThreat(fen, multiplier)).is_err() {
+    if tx.send(EngineCommand::GetThreat(fen)).is_err() {
         eprintln!("[Analyzer] GetThreat send failed");
         return;
     }
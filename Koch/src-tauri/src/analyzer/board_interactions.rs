@@ -1,8 +1,9 @@
 use crate::{
-    analyzer::analyzer::{AnalyzerController, BoardState, MoveKind, UndoInfo},
+    analyzer::analyzer::{BoardState, MoveKind, UndoInfo},
     engine::{
         board::{MoveStruct, PieceMoves},
         move_gen::MoveError,
+        move_tree::ROOT_NODE,
         serializer::{
             serialize_analyzer_controller, SerializedAnalyzerController, SerializedBoard,
         },
@@ -370,109 +371,29 @@ pub fn get_board_at_index(
 ) -> Option<SerializedAnalyzerController> {
     let mut state = state.lock().unwrap();
 
-    let game_moves = &state.analyzer_controller.board.meta_data.move_list;
-
-    if move_index == -1 {
-        let mut starting_board =
-            Board::from(&state.analyzer_controller.board.meta_data.starting_position);
-        starting_board.meta_data = state.analyzer_controller.board.meta_data.clone();
-        let anal = AnalyzerController {
-            game_id: state.analyzer_controller.game_id,
-            board: starting_board,
-            current_ply: -1,
-            board_undo: state.analyzer_controller.board_undo.clone(),
-            last_threat: state.analyzer_controller.last_threat.clone(),
-            last_pv: state.analyzer_controller.last_pv.clone(),
-            chat_history: state.analyzer_controller.chat_history.clone(),
-        };
-        //println!("FEN at index -1: {}", anal.get_fen());
-        let serialized_anal = serialize_analyzer_controller(&anal);
-        return Some(serialized_anal);
-    }
-
-    if move_index < -1 || (move_index as usize) >= game_moves.len() {
+    // Indexes address the main line; sidelines are reached through the tree commands
+    let target = if move_index == -1 {
+        ROOT_NODE
+    } else if move_index < -1 {
         return None;
+    } else {
+        *state
+            .analyzer_controller
+            .board
+            .meta_data
+            .move_tree
+            .mainline()
+            .get(move_index as usize)?
+    };
+
+    if target == state.analyzer_controller.current_node {
+        return Some(serialize_analyzer_controller(&state.analyzer_controller));
     }
-
-    // If requesting the currently loaded ply, return it immediately
-    if move_index as i32 == state.analyzer_controller.current_ply {
-        let serialized_anal = serialize_analyzer_controller(&state.analyzer_controller);
-        return Some(serialized_anal);
-    }
-
-    //compute move delta
-    if move_index > state.analyzer_controller.current_ply as isize {
-        println!("did new move update");
-        //this means the is a forward move
-        let mut starting_board = state.analyzer_controller.board.clone();
-        let mut new_undo = state.analyzer_controller.board_undo.clone();
-        // start from the next ply after current_ply (handles current_ply == -1 safely)
-        let start_index = (state.analyzer_controller.current_ply + 1) as usize;
-        for i in start_index..=(move_index as usize) {
-            let current_move = &game_moves[i];
-            if let Some((from, to, promotion)) = &starting_board.decode_uci_move(&current_move.uci)
-            {
-                match starting_board.move_piece_with_undo(*from, *to, *promotion) {
-                    Ok(undo) => {
-                        //println!("{:#?}", &undo);
-                        new_undo.push(undo);
-                    }
-                    Err(_) => {
-                        return None;
-                    }
-                }
-            } else {
-                return None;
-            }
+    match state.analyzer_controller.goto_node(target) {
+        Ok(_) => Some(serialize_analyzer_controller(&state.analyzer_controller)),
+        Err(e) => {
+            eprintln!("[Analyzer] get_board_at_index({}) failed: {:?}", move_index, e);
+            None
         }
-        starting_board.meta_data = state.analyzer_controller.board.meta_data.clone();
-        starting_board.rerender_move_cache();
-        let anal = AnalyzerController {
-            game_id: state.analyzer_controller.game_id,
-            board: starting_board,
-            current_ply: move_index as i32,
-            board_undo: new_undo,
-            last_threat: state.analyzer_controller.last_threat.clone(),
-            last_pv: state.analyzer_controller.last_pv.clone(),
-            chat_history: state.analyzer_controller.chat_history.clone(),
-        };
-        let serialized_anal = serialize_analyzer_controller(&anal);
-        state.analyzer_controller = anal;
-        //println!("FEN at index {}: {}", move_index, anal.get_fen());
-        return Some(serialized_anal);
-    } else if move_index < state.analyzer_controller.current_ply as isize {
-        let mut starting_board = state.analyzer_controller.board.clone();
-        let mut new_undo = state.analyzer_controller.board_undo.clone();
-        let count = (state.analyzer_controller.current_ply as isize - move_index) as usize;
-        for _ in 0..count {
-            let current_undo = new_undo.pop();
-            if let Some(undo) = current_undo {
-                match starting_board.apply_undo(undo) {
-                    Ok(_) => {}
-                    Err(_) => {
-                        return None;
-                    }
-                }
-            } else {
-                return None;
-            }
-        }
-        println!("did old move update");
-        starting_board.meta_data = state.analyzer_controller.board.meta_data.clone();
-        starting_board.rerender_move_cache();
-        let anal = AnalyzerController {
-            game_id: state.analyzer_controller.game_id,
-            board: starting_board,
-            current_ply: move_index as i32,
-            board_undo: new_undo,
-            last_threat: state.analyzer_controller.last_threat.clone(),
-            last_pv: state.analyzer_controller.last_pv.clone(),
-            chat_history: state.analyzer_controller.chat_history.clone(),
-        };
-        state.analyzer_controller = anal.clone();
-        //println!("FEN at index {}: {}", move_index, anal.get_fen());
-        let serialized_anal = serialize_analyzer_controller(&anal);
-        return Some(serialized_anal);
     }
-    None
 }
@@ -0,0 +1,190 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::analyzer::accuracy::win_percent;
+use crate::analyzer::game_analysis::{
+    capped_cp, load_analysis, mainline_fens, MoveClassification, PlyAnalysis,
+};
+use crate::database::create::get_game_by_id;
+use crate::database::db::state_connection;
+use crate::engine::board::BoardMetaData;
+use crate::engine::PieceColor;
+use crate::server::server::{PvLineData, ServerState};
+
+const MAX_MOMENTS: usize = 5;
+const MIN_MOMENTS: usize = 3;
+// drop in winning chances (0 - 100) that turns a move into a swing
+const SWING_DROP: f64 = 20.0;
+// about +3, and what is left of it after a missed win
+const WINNING: f64 = 75.0;
+const NO_LONGER_WINNING: f64 = 60.0;
+// an only move found is worth about as much as a swing
+const ONLY_MOVE_SCORE: f64 = 25.0;
+// time trouble without a TimeControl tag
+const DEFAULT_LOW_CLOCK: f64 = 30.0;
+
+/// Why a move decided the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub enum CriticalReason {
+    // large drop in winning chances
+    Swing,
+    // the mover was winning and no longer is
+    MissedWin,
+    // a single move held the position and it was found
+    OnlyMove,
+    // mistake or blunder played low on the clock
+    TimeTrouble,
+}
+
+/// A turning point of an analyzed game.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct CriticalMoment {
+    // 1-based half-move of the main line
+    pub ply: u32,
+    pub played: String,
+    pub san: String,
+    pub color: PieceColor,
+    pub best_move: Option<String>,
+    // position before the move
+    pub fen: String,
+    // None for the largest losses picked to reach three moments, which decided nothing
+    pub reason: Option<CriticalReason>,
+    // winning chances of the mover (0 - 100) with the best move and after the move played
+    pub win_before: f32,
+    pub win_after: f32,
+}
+
+/// `[%clk]` value (h:mm:ss, fractions allowed) in seconds.
+pub fn clock_seconds(clock: &str) -> Option<f64> {
+    clock.trim().split(':').try_fold(0.0, |total, part| {
+        part.parse::<f64>().ok().map(|value| total * 60.0 + value)
+    })
+}
+
+// Clock under which a mistake counts as time trouble: a tenth of the base
+// time, at most a minute.
+fn low_clock(time_control: Option<&str>) -> f64 {
+    let base = time_control
+        .and_then(|tc| tc.split(':').next())
+        .filter(|period| !period.contains('/'))
+        .and_then(|period| period.split('+').next())
+        .and_then(|base| base.trim().parse::<f64>().ok());
+    match base {
+        Some(base) => (base / 10.0).min(60.0),
+        None => DEFAULT_LOW_CLOCK,
+    }
+}
+
+// Winning chances of the mover with the best move and with the move played.
+fn win_chances(ply: &PlyAnalysis) -> (f64, f64) {
+    let white_moved = ply.color == PieceColor::White;
+    let after = PvLineData {
+        moves: String::new(),
+        eval_kind: ply.eval_kind.clone(),
+        eval_value: ply.eval_value,
+    };
+    let sign = if white_moved { 1 } else { -1 };
+    let played_cp = capped_cp(&after, !white_moved) * sign;
+    (
+        win_percent(played_cp + ply.cp_loss as i32),
+        win_percent(played_cp),
+    )
+}
+
+fn reason(ply: &PlyAnalysis, before: f64, after: f64, low_on_time: bool) -> Option<CriticalReason> {
+    let mistake = matches!(
+        ply.classification,
+        MoveClassification::Mistake | MoveClassification::Blunder
+    );
+    if before >= WINNING && after < NO_LONGER_WINNING {
+        Some(CriticalReason::MissedWin)
+    } else if mistake && low_on_time {
+        Some(CriticalReason::TimeTrouble)
+    } else if before - after >= SWING_DROP {
+        Some(CriticalReason::Swing)
+    } else if ply.only_move
+        && matches!(
+            ply.classification,
+            MoveClassification::Best | MoveClassification::Excellent
+        )
+    {
+        Some(CriticalReason::OnlyMove)
+    } else {
+        None
+    }
+}
+
+/// The three to five moves that decided the game, in game order. With fewer
+/// than three, the moves losing the most are added without a reason; fewer
+/// still when the game has fewer moves losing anything.
+pub fn critical_moments(metadata: &BoardMetaData, plies: &[PlyAnalysis]) -> Vec<CriticalMoment> {
+    let fens = mainline_fens(metadata);
+    let low_clock = low_clock(metadata.time_control.as_deref());
+    let mut scored: Vec<(f64, Option<CriticalReason>, &PlyAnalysis, (f64, f64))> = plies
+        .iter()
+        .map(|ply| {
+            let (before, after) = win_chances(ply);
+            let low_on_time = metadata
+                .move_list
+                .get(ply.ply as usize - 1)
+                .and_then(|mv| mv.clock.as_deref())
+                .and_then(clock_seconds)
+                .is_some_and(|seconds| seconds < low_clock);
+            let reason = reason(ply, before, after, low_on_time);
+            let score = match reason {
+                Some(CriticalReason::OnlyMove) => ONLY_MOVE_SCORE,
+                _ => before - after,
+            };
+            (score, reason, ply, (before, after))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut picked: Vec<_> = scored
+        .iter()
+        .filter(|(_, reason, _, _)| reason.is_some())
+        .take(MAX_MOMENTS)
+        .collect();
+    if picked.len() < MIN_MOMENTS {
+        let padding: Vec<_> = scored
+            .iter()
+            .filter(|(score, reason, _, _)| reason.is_none() && *score > 0.0)
+            .take(MIN_MOMENTS - picked.len())
+            .collect();
+        picked.extend(padding);
+    }
+    picked.sort_by_key(|(_, _, ply, _)| ply.ply);
+
+    picked
+        .into_iter()
+        .map(|(_, reason, ply, (before, after))| CriticalMoment {
+            ply: ply.ply,
+            played: ply.played.clone(),
+            san: ply.san.clone(),
+            color: ply.color,
+            best_move: ply.best_move.clone(),
+            fen: fens.get(ply.ply as usize - 1).cloned().unwrap_or_default(),
+            reason: *reason,
+            win_before: *before as f32,
+            win_after: *after as f32,
+        })
+        .collect()
+}
+
+/// Turning points of a game with a stored analysis.
+#[tauri::command]
+pub fn get_critical_moments(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    game_id: u32,
+) -> Result<Vec<CriticalMoment>, String> {
+    let con = state_connection(&state)?;
+    let metadata = get_game_by_id(&con, game_id as usize).map_err(|e| e.to_string())?;
+    match load_analysis(&con, game_id, &metadata).map_err(|e| e.to_string())? {
+        Some(analysis) => Ok(critical_moments(&metadata, &analysis.plies)),
+        None => Err("Game has not been analyzed".to_string()),
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::analyzer::game_analysis::{
+    engine_options, judge_move, load_analysis, mainline_fens, white_to_move, Evaluator,
+    MoveClassification, SearchLimit,
+};
+use crate::database::create::get_game_by_id;
+use crate::database::db::Database;
+use crate::database::engines::{engine_for, EngineRole};
+use crate::engine::board::{EvalResponse, EvalType};
+use crate::server::server::{EvalKind, PvLineData, ServerState};
+
+/// Evaluation after one main-line move, for the eval chart.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct EvalPoint {
+    // 1-based half-move of the main line
+    pub ply: u32,
+    pub played: String,
+    pub san: String,
+    // White's point of view; None when the position was never evaluated
+    pub eval_kind: Option<EvalKind>,
+    pub eval_value: Option<i32>,
+    // engine choice in the position before the move
+    pub best_move: Option<String>,
+    // None unless both positions around the move are evaluated
+    pub classification: Option<MoveClassification>,
+}
+
+// `[%eval]` of an imported game, without a line
+fn pgn_eval(eval: &EvalResponse) -> PvLineData {
+    PvLineData {
+        moves: String::new(),
+        eval_kind: match eval.kind {
+            EvalType::Mate => EvalKind::Mate,
+            EvalType::Centipawn => EvalKind::Centipawn,
+        },
+        eval_value: eval.value.round() as i32,
+    }
+}
+
+/// One point per main-line move of a game. A stored analysis is used as is;
+/// otherwise evals come from the eval cache of `evaluator`'s engine at its
+/// depth, then the `[%eval]` comments of the game, and positions still missing
+/// are searched by `evaluator`. Without an evaluator only the comments are
+/// used, so moves aren't judged on evals of other engines or depths. Once
+/// `cancel` is set nothing more is searched and the remaining positions keep
+/// the evals they have.
+pub fn eval_series(
+    database: &Database,
+    game_id: u32,
+    mut evaluator: Option<Evaluator>,
+    cancel: &AtomicBool,
+) -> Result<Vec<EvalPoint>, String> {
+    let metadata = {
+        let con = database.connection().map_err(|e| e.to_string())?;
+        let metadata = get_game_by_id(&con, game_id as usize).map_err(|e| e.to_string())?;
+        if let Some(analysis) =
+            load_analysis(&con, game_id, &metadata).map_err(|e| e.to_string())?
+        {
+            return Ok(analysis
+                .plies
+                .into_iter()
+                .map(|ply| EvalPoint {
+                    ply: ply.ply,
+                    played: ply.played,
+                    san: ply.san,
+                    eval_kind: Some(ply.eval_kind),
+                    eval_value: Some(ply.eval_value),
+                    best_move: ply.best_move,
+                    classification: Some(ply.classification),
+                })
+                .collect());
+        }
+        metadata
+    };
+
+    let fens = mainline_fens(&metadata);
+    let mut lines: Vec<Option<PvLineData>> = Vec::new();
+    for (index, fen) in fens.iter().enumerate() {
+        let cached = match &evaluator {
+            Some(evaluator) => evaluator
+                .cached(database, fen)?
+                .and_then(|mut pv| pv.lines.remove(&1)),
+            None => None,
+        };
+        let from_pgn = || {
+            index
+                .checked_sub(1)
+                .and_then(|mv| metadata.move_list.get(mv))
+                .and_then(|mv| mv.eval.as_ref())
+                .map(pgn_eval)
+        };
+        let line = match (cached.or_else(from_pgn), evaluator.as_mut()) {
+            (Some(line), _) => Some(line),
+            (None, Some(evaluator)) if !cancel.load(Ordering::Relaxed) => evaluator
+                .evaluate(database, fen, cancel)?
+                .and_then(|mut pv| pv.lines.remove(&1)),
+            (None, _) => None,
+        };
+        lines.push(line);
+    }
+
+    Ok(metadata
+        .move_list
+        .iter()
+        .enumerate()
+        .map(|(index, mv)| {
+            let before = lines.get(index).cloned().flatten();
+            let after = lines.get(index + 1).cloned().flatten();
+            let verdict = match (&before, &after) {
+                (Some(before), Some(after)) => Some(judge_move(
+                    before,
+                    after,
+                    white_to_move(&fens[index]),
+                    &mv.uci,
+                )),
+                _ => None,
+            };
+            EvalPoint {
+                ply: index as u32 + 1,
+                played: mv.uci.clone(),
+                san: mv.san.clone(),
+                eval_kind: after.as_ref().map(|line| line.eval_kind.clone()),
+                eval_value: after.as_ref().map(|line| line.eval_value),
+                best_move: before
+                    .as_ref()
+                    .and_then(|line| line.moves.split_whitespace().next())
+                    .map(|best| best.to_string()),
+                classification: verdict.map(|verdict| verdict.classification),
+            }
+        })
+        .collect())
+}
+
+/// Per-ply evaluation of a stored game, normalized to White. With
+/// `fill_missing` positions without an eval are searched with the analysis
+/// settings, which can take a while on an unanalyzed game. Such a fill runs
+/// like a game analysis: it cancels the running one and `cancel_game_analysis`
+/// stops it.
+#[tauri::command]
+pub async fn get_eval_series(
+    state: tauri::State<'_, Mutex<ServerState<'_>>>,
+    game_id: u32,
+    fill_missing: bool,
+) -> Result<Vec<EvalPoint>, String> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (database, settings) = {
+        let mut state = state.lock().map_err(|e| e.to_string())?;
+        if fill_missing {
+            state.analysis_cancel.store(true, Ordering::Relaxed);
+            state.analysis_cancel = cancel.clone();
+        }
+        (state.database.clone(), state.settings.clone())
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        let evaluator = match fill_missing {
+            true => {
+                let engine = database
+                    .connection()
+                    .map(|con| engine_for(&con, &settings, EngineRole::Analyzer))
+                    .map_err(|e| e.to_string())?;
+                Some(Evaluator::start(
+                    &engine,
+                    SearchLimit::from_settings(&settings),
+                    &engine_options(&settings),
+                )?)
+            }
+            false => None,
+        };
+        eval_series(&database, game_id, evaluator, &cancel)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
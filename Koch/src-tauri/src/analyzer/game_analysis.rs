@@ -0,0 +1,709 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use ts_rs::TS;
+
+use crate::analyzer::accuracy::{
+    game_accuracy, move_accuracy, store_accuracy, win_percent, GameAccuracy,
+};
+use crate::analyzer::analyzer::parse_pv_info;
+use crate::analyzer::uci_engine::UciEngine;
+use crate::database::create::get_game_by_id;
+use crate::database::db::{state_connection, Database};
+use crate::database::edit::edit_game;
+use crate::database::engines::{engine_for, EngineConfig, EngineRole};
+use crate::database::eval_cache::{lookup_eval, store_eval};
+use crate::engine::board::{BoardMetaData, EvalResponse, EvalType, GamePhase};
+use crate::engine::move_tree::{MoveTree, ROOT_NODE};
+use crate::engine::{Board, PieceColor};
+use crate::server::server::{EvalKind, PvLineData, PvObject, ServerState, Settings};
+
+pub const ANALYSIS_DEPTH_SETTING: &str = "AnalysisDepth";
+// milliseconds per position, used instead of the depth when set
+pub const ANALYSIS_MOVETIME_SETTING: &str = "AnalysisMoveTime";
+pub const DEFAULT_ANALYSIS_DEPTH: u32 = 18;
+// evals are capped like lichess does, so a lost position can't lose 20 pawns more
+const EVAL_CAP: i32 = 1000;
+// the second line tells whether a single move holds the position
+pub const ANALYSIS_MULTIPV: u32 = 2;
+// drop in winning chances (0 - 100) from the best to the second best move
+// above which the best move is the only one
+const ONLY_MOVE_GAP: f64 = 25.0;
+
+/// How long the engine looks at each position.
+#[derive(Clone, Copy, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub enum SearchLimit {
+    Depth(u32),
+    // milliseconds
+    MoveTime(u32),
+}
+
+impl SearchLimit {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let setting = |key: &str| {
+            settings
+                .map
+                .get(key)
+                .and_then(|value| value.trim().parse::<u32>().ok())
+                .filter(|value| *value > 0)
+        };
+        match setting(ANALYSIS_MOVETIME_SETTING) {
+            Some(movetime) => SearchLimit::MoveTime(movetime),
+            None => SearchLimit::Depth(
+                setting(ANALYSIS_DEPTH_SETTING).unwrap_or(DEFAULT_ANALYSIS_DEPTH),
+            ),
+        }
+    }
+
+    pub fn go_command(&self) -> String {
+        match self {
+            SearchLimit::Depth(depth) => format!("go depth {}", depth),
+            SearchLimit::MoveTime(movetime) => format!("go movetime {}", movetime),
+        }
+    }
+
+    // cached evals at least this deep are good enough
+    fn cache_depth(&self) -> u32 {
+        match self {
+            SearchLimit::Depth(depth) => *depth,
+            SearchLimit::MoveTime(_) => DEFAULT_ANALYSIS_DEPTH,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub enum MoveClassification {
+    Best,
+    Excellent,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveClassification {
+    pub fn from_loss(cp_loss: u32, is_best: bool) -> Self {
+        match cp_loss {
+            _ if is_best => MoveClassification::Best,
+            0 => MoveClassification::Best,
+            1..=20 => MoveClassification::Excellent,
+            21..=50 => MoveClassification::Good,
+            51..=100 => MoveClassification::Inaccuracy,
+            101..=300 => MoveClassification::Mistake,
+            _ => MoveClassification::Blunder,
+        }
+    }
+
+    /// `?!`, `?` and `??`; better moves get no glyph.
+    pub fn nag(&self) -> Option<u8> {
+        match self {
+            MoveClassification::Inaccuracy => Some(6),
+            MoveClassification::Mistake => Some(2),
+            MoveClassification::Blunder => Some(4),
+            _ => None,
+        }
+    }
+}
+
+/// Engine verdict on one main-line move.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct PlyAnalysis {
+    // 1-based half-move of the main line
+    pub ply: u32,
+    pub played: String,
+    pub san: String,
+    pub color: PieceColor,
+    // phase of the position the move was played in
+    pub phase: GamePhase,
+    // engine choice in the position before the move, None when it had no move
+    pub best_move: Option<String>,
+    // eval after the move, from White's point of view; mate 0 means mate on the board
+    pub eval_kind: EvalKind,
+    pub eval_value: i32,
+    pub cp_loss: u32,
+    // 0 - 100, from the drop in winning chances
+    pub accuracy: f32,
+    // every move but the best one loses a lot, whether it was played or not;
+    // false in analyses stored before the second line was searched
+    #[serde(default)]
+    pub only_move: bool,
+    pub classification: MoveClassification,
+}
+
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct GameAnalysis {
+    pub game_id: u32,
+    pub engine: String,
+    pub limit: SearchLimit,
+    pub plies: Vec<PlyAnalysis>,
+    pub accuracy: GameAccuracy,
+    pub analyzed_at: String,
+}
+
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct AnalysisProgress {
+    pub game_id: u32,
+    // positions evaluated, out of one more than the number of moves
+    pub analyzed: u32,
+    pub total: u32,
+    pub percent: f32,
+}
+
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct AnalysisReport {
+    pub game_id: u32,
+    pub analysis: Option<GameAnalysis>,
+    pub cancelled: bool,
+    pub error: Option<String>,
+}
+
+pub fn white_to_move(fen: &str) -> bool {
+    fen.split_whitespace().nth(1) != Some("b")
+}
+
+/// Eval of `line` in centipawns for White, capped at `EVAL_CAP`.
+pub fn capped_cp(line: &PvLineData, white_to_move: bool) -> i32 {
+    match line.eval_kind {
+        EvalKind::Centipawn => line.eval_value.clamp(-EVAL_CAP, EVAL_CAP),
+        EvalKind::Mate if line.eval_value > 0 => EVAL_CAP,
+        EvalKind::Mate if line.eval_value < 0 => -EVAL_CAP,
+        // mate on the board, the side to move is mated
+        EvalKind::Mate if white_to_move => -EVAL_CAP,
+        EvalKind::Mate => EVAL_CAP,
+    }
+}
+
+/// Run `limit` on `fen` and return the lines with their evals normalized to
+/// White. A checkmated or stalemated position comes back as a single line
+/// without moves and depth 0.
+fn search(
+    engine: &mut UciEngine,
+    fen: &str,
+    limit: SearchLimit,
+    cancel: &AtomicBool,
+) -> Result<PvObject, String> {
+    let multiplier = if white_to_move(fen) { 1 } else { -1 };
+    let mut pv = PvObject {
+        fen: fen.to_string(),
+        depth: 0,
+        lines: HashMap::new(),
+        san_lines: HashMap::new(),
+        line_info: HashMap::new(),
+        search_info: None,
+    };
+    engine.ensure_ready().map_err(|e| e.to_string())?;
+    engine
+        .uci_send(&format!("position fen {}", fen))
+        .and_then(|_| engine.uci_send(&limit.go_command()))
+        .map_err(|e| e.to_string())?;
+
+    let mut stopping = false;
+    loop {
+        let line = engine.read_line();
+        if line.starts_with("bestmove") {
+            break;
+        }
+        if line.is_empty() && engine.exited() {
+            return Err("engine exited during the analysis".to_string());
+        }
+        if !stopping && cancel.load(Ordering::Relaxed) {
+            engine.uci_send("stop").ok();
+            stopping = true;
+        }
+        if let Some((index, depth, mut line_data)) = parse_pv_info(&line) {
+            line_data.eval_value *= multiplier;
+            pv.depth = pv.depth.max(depth);
+            pv.lines.insert(index, line_data);
+        } else if line.starts_with("info depth 0 ") && pv.lines.is_empty() {
+            let eval_kind = if line.contains(" score mate ") {
+                EvalKind::Mate
+            } else {
+                EvalKind::Centipawn
+            };
+            pv.lines.insert(
+                1,
+                PvLineData {
+                    moves: String::new(),
+                    eval_kind,
+                    eval_value: 0,
+                },
+            );
+        }
+    }
+    Ok(pv)
+}
+
+pub struct Verdict {
+    pub best_move: Option<String>,
+    pub cp_loss: u32,
+    pub accuracy: f32,
+    pub classification: MoveClassification,
+}
+
+/// Verdict on `played` from the first lines (White's side) before and after it.
+pub fn judge_move(
+    before: &PvLineData,
+    after: &PvLineData,
+    white_moved: bool,
+    played: &str,
+) -> Verdict {
+    let sign = if white_moved { 1 } else { -1 };
+    let best_cp = capped_cp(before, white_moved) * sign;
+    let played_cp = capped_cp(after, !white_moved) * sign;
+    let cp_loss = (best_cp - played_cp).max(0) as u32;
+    // both evals are from the mover's side, so are the winning chances
+    let accuracy = move_accuracy(win_percent(best_cp), win_percent(played_cp));
+    let best_move = before
+        .moves
+        .split_whitespace()
+        .next()
+        .map(|best| best.to_string());
+    let is_best = best_move.as_deref() == Some(played);
+    Verdict {
+        best_move,
+        cp_loss: if is_best { 0 } else { cp_loss },
+        accuracy: if is_best { 100.0 } else { accuracy as f32 },
+        classification: MoveClassification::from_loss(cp_loss, is_best),
+    }
+}
+
+/// FEN of the start position followed by the FEN after every main-line move.
+pub fn mainline_fens(metadata: &BoardMetaData) -> Vec<String> {
+    let tree = &metadata.move_tree;
+    let mut fens = vec![metadata.starting_position.clone()];
+    fens.extend(
+        tree.mainline()
+            .iter()
+            .filter_map(|id| tree.get(*id).map(|node| node.fen.clone())),
+    );
+    fens
+}
+
+/// Whether the second line of `pv` is much worse than the first for the side to move.
+fn is_only_move(pv: &PvObject, white_to_move: bool) -> bool {
+    let (Some(best), Some(second)) = (pv.lines.get(&1), pv.lines.get(&2)) else {
+        return false;
+    };
+    let sign = if white_to_move { 1 } else { -1 };
+    win_percent(capped_cp(best, white_to_move) * sign)
+        - win_percent(capped_cp(second, white_to_move) * sign)
+        >= ONLY_MOVE_GAP
+}
+
+/// Centipawn loss and verdict of every main-line move. `evals[i]` holds the
+/// lines in the position before move `i`, `evals[moves.len()]` those after
+/// the last move.
+pub fn classify_moves(tree: &MoveTree, evals: &[PvObject]) -> Vec<PlyAnalysis> {
+    let mut plies = Vec::new();
+    let mut fen_before = tree
+        .get(ROOT_NODE)
+        .map(|root| root.fen.clone())
+        .unwrap_or_default();
+    for (index, id) in tree.mainline().into_iter().enumerate() {
+        let (Some(node), Some(position), Some(before), Some(after)) = (
+            tree.get(id),
+            evals.get(index),
+            evals.get(index).and_then(|pv| pv.lines.get(&1)),
+            evals.get(index + 1).and_then(|pv| pv.lines.get(&1)),
+        ) else {
+            break;
+        };
+        let Some(mv) = node.mv.as_ref() else {
+            break;
+        };
+        let white_moved = white_to_move(&fen_before);
+        let verdict = judge_move(before, after, white_moved, &mv.uci);
+        plies.push(PlyAnalysis {
+            ply: index as u32 + 1,
+            played: mv.uci.clone(),
+            san: mv.san.clone(),
+            color: if white_moved {
+                PieceColor::White
+            } else {
+                PieceColor::Black
+            },
+            phase: Board::from(&fen_before).game_phase,
+            best_move: verdict.best_move,
+            eval_kind: after.eval_kind.clone(),
+            eval_value: after.eval_value,
+            cp_loss: verdict.cp_loss,
+            accuracy: verdict.accuracy,
+            only_move: is_only_move(position, white_moved),
+            classification: verdict.classification,
+        });
+        fen_before = node.fen.clone();
+    }
+    plies
+}
+
+/// Write evals and `?!` / `?` / `??` of `plies` into the main line of `tree`,
+/// as far as its moves still match. Moves that already have an eval or a
+/// move-quality glyph (`!`, `?`, `!!`, `??`, `!?`, `?!`) keep them.
+pub fn apply_analysis(tree: &mut MoveTree, plies: &[PlyAnalysis]) {
+    for (id, ply) in tree.mainline().into_iter().zip(plies) {
+        let Some(node) = tree.get_mut(id) else {
+            break;
+        };
+        let Some(mv) = node.mv.as_mut() else {
+            break;
+        };
+        if mv.uci != ply.played {
+            break;
+        }
+        if mv.eval.is_none() {
+            mv.eval = match ply.eval_kind {
+                EvalKind::Mate if ply.eval_value == 0 => None,
+                EvalKind::Mate => Some(EvalResponse {
+                    value: ply.eval_value as f32,
+                    kind: EvalType::Mate,
+                }),
+                EvalKind::Centipawn => Some(EvalResponse {
+                    value: ply.eval_value as f32,
+                    kind: EvalType::Centipawn,
+                }),
+            };
+        }
+        let graded = node.nags.iter().any(|nag| (1..=6).contains(nag));
+        if let (false, Some(nag)) = (graded, ply.classification.nag()) {
+            node.nags.insert(0, nag);
+        }
+    }
+}
+
+pub fn store_analysis(con: &Connection, analysis: &GameAnalysis) -> rusqlite::Result<()> {
+    let plies = serde_json::to_string(&analysis.plies)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    let (depth, movetime) = match analysis.limit {
+        SearchLimit::Depth(depth) => (Some(depth), None),
+        SearchLimit::MoveTime(movetime) => (None, Some(movetime)),
+    };
+    con.execute(
+        "INSERT OR REPLACE INTO game_analysis
+            (game_id, engine, depth, movetime, plies, analyzed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))",
+        params![analysis.game_id, analysis.engine, depth, movetime, plies],
+    )?;
+    store_accuracy(con, analysis.game_id, &analysis.accuracy)
+}
+
+/// Stored analysis of a game, None if there is none or its moves no longer
+/// match the main line of `metadata`.
+pub fn load_analysis(
+    con: &Connection,
+    game_id: u32,
+    metadata: &BoardMetaData,
+) -> rusqlite::Result<Option<GameAnalysis>> {
+    let row: Option<(String, Option<u32>, Option<u32>, String, Option<String>)> = con
+        .query_row(
+            "SELECT engine, depth, movetime, plies, analyzed_at
+             FROM game_analysis WHERE game_id = ?1",
+            [game_id],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .optional()?;
+    let Some((engine, depth, movetime, plies, analyzed_at)) = row else {
+        return Ok(None);
+    };
+    let plies: Vec<PlyAnalysis> = match serde_json::from_str(&plies) {
+        Ok(plies) => plies,
+        Err(e) => {
+            eprintln!("[DB] ignoring unreadable analysis of game {}: {e}", game_id);
+            return Ok(None);
+        }
+    };
+    let same_moves = plies.len() == metadata.move_list.len()
+        && plies
+            .iter()
+            .zip(&metadata.move_list)
+            .all(|(ply, mv)| ply.played == mv.uci);
+    if !same_moves {
+        return Ok(None);
+    }
+    let limit = match (depth, movetime) {
+        (_, Some(movetime)) => SearchLimit::MoveTime(movetime),
+        (depth, None) => SearchLimit::Depth(depth.unwrap_or(DEFAULT_ANALYSIS_DEPTH)),
+    };
+    Ok(Some(GameAnalysis {
+        game_id,
+        engine,
+        limit,
+        accuracy: game_accuracy(&plies),
+        plies,
+        analyzed_at: analyzed_at.unwrap_or_default(),
+    }))
+}
+
+/// Engine settings of the analyzer that batch jobs use too.
+pub fn engine_options(settings: &Settings) -> Vec<(&'static str, String)> {
+    let setting = |key: &str, default: &str| {
+        settings
+            .map
+            .get(key)
+            .cloned()
+            .unwrap_or(default.to_string())
+    };
+    vec![
+        ("Threads", setting("Threads", "1")),
+        ("Hash", setting("HashSize", "128")),
+    ]
+}
+
+/// Engine of a batch job, searching `ANALYSIS_MULTIPV` lines. Cached evals
+/// deep enough for `limit` are reused, new ones are added to the cache.
+pub struct Evaluator {
+    engine: UciEngine,
+    pub engine_id: String,
+    limit: SearchLimit,
+}
+
+impl Evaluator {
+    pub fn start(
+        config: &EngineConfig,
+        limit: SearchLimit,
+        engine_options: &[(&str, String)],
+    ) -> Result<Self, String> {
+        let (mut engine, engine_id) = config.launch()?;
+        let multipv = ANALYSIS_MULTIPV.to_string();
+        for (name, value) in engine_options.iter().chain([&("MultiPV", multipv)]) {
+            if let Err(e) = engine.set_option(name, value) {
+                eprintln!("[Analyzer] could not set {} for the analysis: {e}", name);
+            }
+        }
+        Ok(Self {
+            engine,
+            engine_id,
+            limit,
+        })
+    }
+
+    /// Cached lines in `fen` by this engine, if they are as deep as the limit.
+    pub fn cached(&self, database: &Database, fen: &str) -> Result<Option<PvObject>, String> {
+        let con = database.connection().map_err(|e| e.to_string())?;
+        Ok(lookup_eval(&con, fen, &self.engine_id, ANALYSIS_MULTIPV)
+            .inspect_err(|e| eprintln!("[Analyzer] eval cache lookup failed: {e}"))
+            .ok()
+            .flatten()
+            .filter(|pv| pv.depth >= self.limit.cache_depth()))
+    }
+
+    /// Lines in `fen` with their evals normalized to White, None if cancelled.
+    /// There is always a first line.
+    pub fn evaluate(
+        &mut self,
+        database: &Database,
+        fen: &str,
+        cancel: &AtomicBool,
+    ) -> Result<Option<PvObject>, String> {
+        let pv = match self.cached(database, fen)? {
+            Some(pv) => pv,
+            None => {
+                let pv = search(&mut self.engine, fen, self.limit, cancel)?;
+                if cancel.load(Ordering::Relaxed) {
+                    return Ok(None);
+                }
+                if pv.depth > 0 {
+                    database
+                        .connection()
+                        .and_then(|con| {
+                            store_eval(&con, fen, &self.engine_id, ANALYSIS_MULTIPV, &pv)
+                        })
+                        .inspect_err(|e| eprintln!("[Analyzer] could not cache eval: {e}"))
+                        .ok();
+                }
+                pv
+            }
+        };
+        match pv.lines.contains_key(&1) {
+            true => Ok(Some(pv)),
+            false => Err(format!("no evaluation for {}", fen)),
+        }
+    }
+}
+
+fn run_analysis(
+    app: &AppHandle,
+    database: &Database,
+    game_id: u32,
+    engine: &EngineConfig,
+    limit: SearchLimit,
+    engine_options: &[(&str, String)],
+    cancel: &AtomicBool,
+) -> Result<Option<GameAnalysis>, String> {
+    let metadata = {
+        let con = database.connection().map_err(|e| e.to_string())?;
+        get_game_by_id(&con, game_id as usize).map_err(|e| e.to_string())?
+    };
+    let fens = mainline_fens(&metadata);
+    let mut evaluator = Evaluator::start(engine, limit, engine_options)?;
+
+    let total = fens.len() as u32;
+    let mut evals = Vec::new();
+    for (index, fen) in fens.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let Some(pv) = evaluator.evaluate(database, fen, cancel)? else {
+            return Ok(None);
+        };
+        evals.push(pv);
+
+        let analyzed = index as u32 + 1;
+        app.emit(
+            "analysis_progress",
+            AnalysisProgress {
+                game_id,
+                analyzed,
+                total,
+                percent: analyzed as f32 / total as f32 * 100.0,
+            },
+        )
+        .inspect_err(|e| eprintln!("[Analyzer] failed to emit progress: {e}"))
+        .ok();
+    }
+    let engine_id = evaluator.engine_id.clone();
+    drop(evaluator);
+
+    let plies = classify_moves(&metadata.move_tree, &evals);
+    let mut con = database.connection().map_err(|e| e.to_string())?;
+    // the game may have been edited meanwhile, annotate what is stored now
+    let mut stored = get_game_by_id(&con, game_id as usize).map_err(|e| e.to_string())?;
+    let same_moves = stored.move_list.len() == metadata.move_list.len()
+        && stored
+            .move_list
+            .iter()
+            .zip(&metadata.move_list)
+            .all(|(stored, analyzed)| stored.uci == analyzed.uci);
+    if !same_moves {
+        return Err("The game was edited during the analysis".to_string());
+    }
+    apply_analysis(&mut stored.move_tree, &plies);
+    stored.move_list = stored.move_tree.mainline_moves();
+    let undo = edit_game(&mut con, game_id as i64, &stored).map_err(|e| e.to_string())?;
+    store_analysis(
+        &con,
+        &GameAnalysis {
+            game_id,
+            engine: engine_id,
+            limit,
+            accuracy: game_accuracy(&plies),
+            plies: plies.clone(),
+            analyzed_at: String::new(),
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    if let Ok(mut state) = app.state::<Mutex<ServerState>>().lock() {
+        // the annotations can be reverted like any other edit
+        state.game_undo = Some(undo);
+        let analyzer = &mut state.analyzer_controller;
+        if analyzer.game_id == game_id as usize {
+            apply_analysis(&mut analyzer.board.meta_data.move_tree, &plies);
+            analyzer.sync_move_list();
+        }
+    }
+    load_analysis(&con, game_id, &stored).map_err(|e| e.to_string())
+}
+
+/// Analyze every main-line move of a stored game in the background, with
+/// `depth` or `movetime` (milliseconds) per position, or the analysis
+/// settings when both are None. Progress is reported through
+/// `analysis_progress` events and the result through `analysis_finished`.
+/// Starting an analysis cancels the one running.
+#[tauri::command]
+pub fn analyze_game(
+    app: AppHandle,
+    state: tauri::State<'_, Mutex<ServerState>>,
+    game_id: u32,
+    depth: Option<u32>,
+    movetime: Option<u32>,
+) -> Result<(), String> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (database, settings, limit, engine_options) = {
+        let mut state = state.lock().unwrap();
+        state.analysis_cancel.store(true, Ordering::Relaxed);
+        state.analysis_cancel = cancel.clone();
+        let limit = match (depth, movetime) {
+            (_, Some(movetime)) => SearchLimit::MoveTime(movetime),
+            (Some(depth), None) => SearchLimit::Depth(depth),
+            (None, None) => SearchLimit::from_settings(&state.settings),
+        };
+        (
+            state.database.clone(),
+            state.settings.clone(),
+            limit,
+            engine_options(&state.settings),
+        )
+    };
+    let engine = database
+        .connection()
+        .map(|con| engine_for(&con, &settings, EngineRole::Analyzer))
+        .map_err(|e| e.to_string())?;
+
+    thread::spawn(move || {
+        let mut report = AnalysisReport {
+            game_id,
+            ..Default::default()
+        };
+        match run_analysis(
+            &app,
+            &database,
+            game_id,
+            &engine,
+            limit,
+            &engine_options,
+            &cancel,
+        ) {
+            Ok(Some(analysis)) => report.analysis = Some(analysis),
+            Ok(None) => report.cancelled = true,
+            Err(e) => {
+                eprintln!("[Analyzer] analysis of game {} stopped: {}", game_id, e);
+                report.error = Some(e);
+            }
+        }
+        println!(
+            "[Analyzer] game {} analyzed{}",
+            game_id,
+            if report.cancelled { " (cancelled)" } else { "" }
+        );
+        app.emit("analysis_finished", report)
+            .inspect_err(|e| eprintln!("[Analyzer] failed to emit analysis: {e}"))
+            .ok();
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn cancel_game_analysis(state: tauri::State<'_, Mutex<ServerState>>) {
+    let state = state.lock().unwrap();
+    state.analysis_cancel.store(true, Ordering::Relaxed);
+}
+
+/// Stored analysis of a game, None until `analyze_game` ran on its current moves.
+#[tauri::command]
+pub fn get_game_analysis(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    game_id: u32,
+) -> Result<Option<GameAnalysis>, String> {
+    let con = state_connection(&state)?;
+    let metadata = get_game_by_id(&con, game_id as usize).map_err(|e| e.to_string())?;
+    load_analysis(&con, game_id, &metadata).map_err(|e| e.to_string())
+}
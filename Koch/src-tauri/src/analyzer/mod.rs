@@ -1,2 +1,9 @@
+pub mod accuracy;
 pub mod analyzer;
 pub mod board_interactions;
+pub mod critical;
+pub mod eval_series;
+pub mod game_analysis;
+pub mod threat;
+pub mod uci_engine;
+pub mod variations;
@@ -0,0 +1,251 @@
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use ts_rs::TS;
+
+use crate::analyzer::analyzer::scratch_board;
+use crate::analyzer::uci_engine::{parse_info, UciEngine, UciInfo};
+use crate::database::engines::EngineConfig;
+use crate::server::server::{PvLineData, SanLine, ServerState};
+
+const THREAT_GO_COMMAND: &str = "go depth 15";
+
+/// What the side that just moved threatens: the line it would play if it
+/// could move again.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct Threat {
+    // position asked about, before the null move
+    pub fen: String,
+    // the side to move is in check, so it can't pass and there is no line
+    pub in_check: bool,
+    pub depth: u32,
+    // eval from White's side, moves in UCI
+    pub line: Option<PvLineData>,
+    pub san: SanLine,
+}
+
+impl fmt::Display for Threat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.line {
+            _ if self.in_check => write!(f, "The side to move is in check"),
+            Some(line) if !self.san.moves.is_empty() => write!(
+                f,
+                "{}",
+                PvLineData {
+                    moves: self.san.text.clone(),
+                    eval_kind: line.eval_kind.clone(),
+                    eval_value: line.eval_value,
+                }
+            ),
+            Some(line) => write!(f, "{}", line),
+            None => write!(f, "No Threat"),
+        }
+    }
+}
+
+pub enum ThreatRequest {
+    // find the threat in this position; a newer request cancels an older one
+    Search(String),
+    SetEngine(EngineConfig),
+}
+
+/// `fen` with the side to move passing: the turn changes, there is no en
+/// passant square and the clocks move on.
+pub fn null_move_fen(fen: &str) -> String {
+    let mut parts: Vec<String> = fen.split_whitespace().map(str::to_string).collect();
+    if parts.len() < 2 {
+        return fen.to_string();
+    }
+    let black_passes = parts[1] == "b";
+    parts[1] = if black_passes { "w" } else { "b" }.to_string();
+    if let Some(en_passant) = parts.get_mut(3) {
+        *en_passant = "-".to_string();
+    }
+    if let Some(halfmove) = parts.get_mut(4) {
+        *halfmove = (halfmove.parse::<u32>().unwrap_or(0) + 1).to_string();
+    }
+    if let (true, Some(fullmove)) = (black_passes, parts.get_mut(5)) {
+        *fullmove = (fullmove.parse::<u32>().unwrap_or(1) + 1).to_string();
+    }
+    parts.join(" ")
+}
+
+/// Start the worker that searches threats on its own engine, kept running
+/// between requests. It stops when the sender is dropped.
+pub fn spawn_threat_worker(app_handle: AppHandle, config: EngineConfig) -> Sender<ThreatRequest> {
+    let (tx, rx) = mpsc::channel();
+    let worker = ThreatWorker {
+        app_handle,
+        rx,
+        config,
+        engine: None,
+        stale_engine: false,
+        next_fen: None,
+        closed: false,
+    };
+    thread::spawn(move || worker.run());
+    tx
+}
+
+struct ThreatWorker {
+    app_handle: AppHandle,
+    rx: Receiver<ThreatRequest>,
+    config: EngineConfig,
+    // started on the first request
+    engine: Option<UciEngine>,
+    // the engine was switched, restart before the next search
+    stale_engine: bool,
+    // newest position asked about and not searched yet
+    next_fen: Option<String>,
+    closed: bool,
+}
+
+impl ThreatWorker {
+    fn run(mut self) {
+        while !self.closed {
+            let Some(fen) = self.next_fen.take() else {
+                match self.rx.recv() {
+                    Ok(request) => self.apply(request),
+                    Err(_) => break,
+                }
+                continue;
+            };
+            if let Some(threat) = self.threat(fen) {
+                self.publish(threat);
+            }
+        }
+        println!("[Threat] Worker exited");
+    }
+
+    fn apply(&mut self, request: ThreatRequest) {
+        match request {
+            ThreatRequest::Search(fen) => self.next_fen = Some(fen),
+            ThreatRequest::SetEngine(config) => {
+                self.config = config;
+                self.stale_engine = true;
+            }
+        }
+    }
+
+    // Take the waiting requests; true when the running search is outdated.
+    fn poll(&mut self) -> bool {
+        loop {
+            match self.rx.try_recv() {
+                Ok(request) => self.apply(request),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        self.closed || self.stale_engine || self.next_fen.is_some()
+    }
+
+    fn take_engine(&mut self) -> Option<UciEngine> {
+        if std::mem::take(&mut self.stale_engine) {
+            self.engine = None;
+        }
+        if let Some(engine) = self.engine.take() {
+            return Some(engine);
+        }
+        match self.config.launch() {
+            Ok((mut engine, name)) => {
+                engine.set_option("MultiPV", "1").ok();
+                println!("[Threat] Engine: {}", name);
+                Some(engine)
+            }
+            Err(e) => {
+                eprintln!("[Threat] Failed to start engine: {e}");
+                None
+            }
+        }
+    }
+
+    // Threat in `fen`, None when the search failed or was cancelled.
+    fn threat(&mut self, fen: String) -> Option<Threat> {
+        let board = scratch_board(&fen)?;
+        if board.is_in_check(board.turn) {
+            return Some(Threat {
+                fen,
+                in_check: true,
+                depth: 0,
+                line: None,
+                san: SanLine::default(),
+            });
+        }
+        let null_fen = null_move_fen(&fen);
+        let null_board = scratch_board(&null_fen)?;
+        let multiplier = if null_fen.split_whitespace().nth(1) == Some("b") {
+            -1
+        } else {
+            1
+        };
+
+        let mut engine = self.take_engine()?;
+        let started = engine
+            .ensure_ready()
+            .and_then(|_| engine.set_fen_position(&null_fen))
+            .and_then(|_| engine.uci_send(THREAT_GO_COMMAND));
+        if let Err(e) = started {
+            eprintln!("[Threat] Search failed: {e}");
+            return None;
+        }
+
+        let mut best: Option<UciInfo> = None;
+        let mut cancelled = false;
+        loop {
+            let line = engine.read_line();
+            if line.starts_with("bestmove") {
+                break;
+            }
+            if line.is_empty() && engine.exited() {
+                eprintln!("[Threat] Engine exited");
+                return None;
+            }
+            if !cancelled && self.poll() {
+                engine.uci_send("stop").ok();
+                cancelled = true;
+            }
+            if let Some(info) = parse_info(&line).filter(|info| info.multipv == 1) {
+                if info.line().is_some() {
+                    best = Some(info);
+                }
+            }
+        }
+        self.engine = Some(engine);
+        if cancelled {
+            return None;
+        }
+
+        // no line when the other side has no move either
+        let info = best.map(|mut info| {
+            info.normalize(multiplier);
+            info
+        });
+        let line = info.as_ref().and_then(UciInfo::line);
+        Some(Threat {
+            fen,
+            in_check: false,
+            depth: info.and_then(|info| info.depth).unwrap_or(0),
+            san: line
+                .as_ref()
+                .map(|line| SanLine::from_uci(&null_board, &line.moves))
+                .unwrap_or_default(),
+            line,
+        })
+    }
+
+    fn publish(&self, threat: Threat) {
+        println!("[Threat] {}: {}", threat.fen, threat);
+        if let Ok(mut global_state) = self.app_handle.state::<Mutex<ServerState>>().lock() {
+            global_state.analyzer_controller.last_threat = Some(threat.clone());
+        }
+        let _ = self.app_handle.emit("threat_update", threat);
+    }
+}
@@ -0,0 +1,520 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::server::server::{EvalKind, PvLineData};
+
+/// Value type of a UCI option, with its default and bounds.
+#[derive(Clone, Debug, PartialEq, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub enum UciOptionKind {
+    Spin { default: i64, min: i64, max: i64 },
+    Check { default: bool },
+    Combo { default: String, vars: Vec<String> },
+    String { default: String },
+    Button,
+}
+
+/// An `option name ... type ...` declaration of the `uci` handshake.
+#[derive(Clone, Debug, PartialEq, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct UciOption {
+    pub name: String,
+    pub kind: UciOptionKind,
+}
+
+impl UciOption {
+    /// `value` written the way `setoption` expects it, or why it doesn't fit.
+    pub fn check_value(&self, value: &str) -> Result<String, String> {
+        let value = value.trim();
+        match &self.kind {
+            UciOptionKind::Spin { min, max, .. } => match value.parse::<i64>() {
+                Ok(number) if (*min..=*max).contains(&number) => Ok(number.to_string()),
+                _ => Err(format!("{} must be between {} and {}", self.name, min, max)),
+            },
+            UciOptionKind::Check { .. } => match value {
+                "true" | "false" => Ok(value.to_string()),
+                _ => Err(format!("{} must be true or false", self.name)),
+            },
+            UciOptionKind::Combo { vars, .. } => vars
+                .iter()
+                .find(|var| var.eq_ignore_ascii_case(value))
+                .cloned()
+                .ok_or_else(|| format!("{} must be one of {}", self.name, vars.join(", "))),
+            UciOptionKind::String { .. } => Ok(value.to_string()),
+            UciOptionKind::Button => Err(format!("{} has no value", self.name)),
+        }
+    }
+}
+
+// a program that doesn't answer `uci` by then isn't a UCI engine
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const OPTION_KEYWORDS: [&str; 5] = ["type", "default", "min", "max", "var"];
+
+/// Parse an `option` line of the `uci` handshake. Names and string values may
+/// contain spaces, `<empty>` stands for an empty string.
+pub fn parse_option_line(line: &str) -> Option<UciOption> {
+    let rest = line.trim().strip_prefix("option name ")?;
+    let (name, rest) = rest.split_once(" type ")?;
+    let mut tokens = rest.split_whitespace();
+    let kind = tokens.next()?;
+
+    // keyword -> value, `var` can repeat
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    for token in tokens {
+        match fields.last_mut() {
+            _ if OPTION_KEYWORDS.contains(&token) => fields.push((token, String::new())),
+            Some((_, value)) if value.is_empty() => value.push_str(token),
+            Some((_, value)) => {
+                value.push(' ');
+                value.push_str(token);
+            }
+            None => {}
+        }
+    }
+    let field = |key: &str| {
+        fields
+            .iter()
+            .find(|(keyword, _)| *keyword == key)
+            .map(|(_, value)| match value.as_str() {
+                "<empty>" => String::new(),
+                value => value.to_string(),
+            })
+    };
+    let number = |key: &str| field(key).and_then(|value| value.parse::<i64>().ok());
+
+    let kind = match kind {
+        "spin" => UciOptionKind::Spin {
+            default: number("default").unwrap_or(0),
+            min: number("min").unwrap_or(i64::MIN),
+            max: number("max").unwrap_or(i64::MAX),
+        },
+        "check" => UciOptionKind::Check {
+            default: field("default").as_deref() == Some("true"),
+        },
+        "combo" => UciOptionKind::Combo {
+            default: field("default").unwrap_or_default(),
+            vars: fields
+                .iter()
+                .filter(|(keyword, _)| *keyword == "var")
+                .map(|(_, value)| value.clone())
+                .collect(),
+        },
+        "string" => UciOptionKind::String {
+            default: field("default").unwrap_or_default(),
+        },
+        "button" => UciOptionKind::Button,
+        _ => return None,
+    };
+    Some(UciOption {
+        name: name.trim().to_string(),
+        kind,
+    })
+}
+
+/// Win, draw and loss chances in permille, sent with `UCI_ShowWDL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct Wdl {
+    pub win: u32,
+    pub draw: u32,
+    pub loss: u32,
+}
+
+/// Everything an `info` line reports. Fields the engine left out are None.
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct UciInfo {
+    pub multipv: u8,
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    // milliseconds
+    pub time: Option<u64>,
+    // permille of the hash table in use
+    pub hashfull: Option<u32>,
+    pub tbhits: Option<u64>,
+    pub currmove: Option<String>,
+    pub currmovenumber: Option<u32>,
+    pub eval_kind: Option<EvalKind>,
+    pub eval_value: Option<i32>,
+    pub wdl: Option<Wdl>,
+    // the score is only a bound, from a fail high or fail low
+    pub lowerbound: bool,
+    pub upperbound: bool,
+    pub pv: Option<String>,
+}
+
+impl UciInfo {
+    /// Turn the score and WDL, from the side to move, into White's view when
+    /// `multiplier` is -1.
+    pub fn normalize(&mut self, multiplier: i32) {
+        if multiplier >= 0 {
+            return;
+        }
+        self.eval_value = self.eval_value.map(|value| -value);
+        if let Some(wdl) = &mut self.wdl {
+            std::mem::swap(&mut wdl.win, &mut wdl.loss);
+        }
+        std::mem::swap(&mut self.lowerbound, &mut self.upperbound);
+    }
+
+    /// The line of an info with a `pv`. None when its score is only a bound:
+    /// the search of that line isn't finished and its eval is not exact.
+    pub fn line(&self) -> Option<PvLineData> {
+        if self.lowerbound || self.upperbound {
+            return None;
+        }
+        Some(PvLineData {
+            moves: self.pv.clone()?,
+            eval_kind: self.eval_kind.clone().unwrap_or(EvalKind::Centipawn),
+            eval_value: self.eval_value.unwrap_or(0),
+        })
+    }
+}
+
+/// Parse an `info` line. `info string` lines and other output give None.
+pub fn parse_info(line: &str) -> Option<UciInfo> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("info") {
+        return None;
+    }
+    let tokens: Vec<&str> = tokens.collect();
+    if tokens.first().is_none_or(|token| *token == "string") {
+        return None;
+    }
+    let mut info = UciInfo {
+        multipv: 1,
+        ..Default::default()
+    };
+    let mut i = 0;
+    while i < tokens.len() {
+        let next = tokens.get(i + 1).copied().unwrap_or_default();
+        match tokens[i] {
+            "multipv" => info.multipv = next.parse().unwrap_or(1),
+            "depth" => info.depth = next.parse().ok(),
+            "seldepth" => info.seldepth = next.parse().ok(),
+            "nodes" => info.nodes = next.parse().ok(),
+            "nps" => info.nps = next.parse().ok(),
+            "time" => info.time = next.parse().ok(),
+            "hashfull" => info.hashfull = next.parse().ok(),
+            "tbhits" => info.tbhits = next.parse().ok(),
+            "currmove" => info.currmove = Some(next.to_string()).filter(|mv| !mv.is_empty()),
+            "currmovenumber" => info.currmovenumber = next.parse().ok(),
+            "score" => {
+                info.eval_kind = match next {
+                    "mate" => Some(EvalKind::Mate),
+                    "cp" => Some(EvalKind::Centipawn),
+                    _ => None,
+                };
+                info.eval_value = tokens.get(i + 2).and_then(|value| value.parse().ok());
+                i += 1;
+            }
+            "lowerbound" => {
+                info.lowerbound = true;
+                i += 1;
+                continue;
+            }
+            "upperbound" => {
+                info.upperbound = true;
+                i += 1;
+                continue;
+            }
+            "wdl" => {
+                let chances: Vec<u32> = tokens[i + 1..]
+                    .iter()
+                    .take(3)
+                    .map_while(|value| value.parse().ok())
+                    .collect();
+                if let [win, draw, loss] = chances[..] {
+                    info.wdl = Some(Wdl { win, draw, loss });
+                }
+                i += chances.len() + 1;
+                continue;
+            }
+            "pv" => {
+                info.pv = Some(tokens[i + 1..].join(" "));
+                break;
+            }
+            // the rest of the line is free text
+            "string" => break,
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+    Some(info)
+}
+
+/// A UCI engine process. Lines are read blocking; an engine that exited
+/// reads as empty lines.
+pub struct UciEngine {
+    child: Child,
+    stdin: ChildStdin,
+    // output lines, read on their own thread so reads can time out
+    lines: Receiver<String>,
+    // declared in the handshake
+    options: Vec<UciOption>,
+}
+
+impl UciEngine {
+    pub fn spawn(path: &str, args: &[String]) -> io::Result<Self> {
+        let mut child = Command::new(path)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            child.kill().ok();
+            return Err(io::Error::other("engine pipes unavailable"));
+        };
+        let (tx, lines) = mpsc::channel();
+        // ends when the engine closes its output or the engine is dropped
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if tx.send(line.trim_end().to_string()).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(UciEngine {
+            child,
+            stdin,
+            lines,
+            options: Vec::new(),
+        })
+    }
+
+    pub fn uci_send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+
+    pub fn read_line(&mut self) -> String {
+        self.lines.recv().unwrap_or_default()
+    }
+
+    pub fn ensure_ready(&mut self) -> io::Result<()> {
+        self.uci_send("isready")?;
+        loop {
+            let line = self.read_line();
+            if line == "readyok" {
+                return Ok(());
+            }
+            if line.is_empty() && self.child.try_wait()?.is_some() {
+                return Err(io::Error::other("engine exited"));
+            }
+        }
+    }
+
+    /// Run the `uci` handshake: the "id name" of the engine and its options.
+    /// A program that doesn't finish it within `HANDSHAKE_TIMEOUT` is killed.
+    pub fn handshake(&mut self) -> io::Result<(String, Vec<UciOption>)> {
+        self.uci_send("uci")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        let mut name = String::from("unknown engine");
+        let mut options = Vec::new();
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let line = match self.lines.recv_timeout(timeout) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.child.kill().ok();
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "no answer to the uci handshake, is this a UCI engine?",
+                    ));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::other("engine exited during the uci handshake"));
+                }
+            };
+            if let Some(id) = line.trim().strip_prefix("id name ") {
+                name = id.to_string();
+            } else if let Some(option) = parse_option_line(&line) {
+                options.push(option);
+            } else if line.trim() == "uciok" {
+                self.options = options.clone();
+                return Ok((name, options));
+            }
+        }
+    }
+
+    pub fn exited(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(Some(_)))
+    }
+
+    /// Whether the engine declared option `name` in the handshake.
+    pub fn supports(&self, name: &str) -> bool {
+        self.options.iter().any(|option| option.name == name)
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> io::Result<()> {
+        self.uci_send(&format!("setoption name {} value {}", name, value))
+    }
+
+    pub fn set_fen_position(&mut self, fen: &str) -> io::Result<()> {
+        self.uci_send(&format!("position fen {}", fen))
+    }
+
+    pub fn setup_for_new_game(&mut self) -> io::Result<()> {
+        self.uci_send("ucinewgame")?;
+        self.ensure_ready()
+    }
+
+    /// Play at `elo`, for engines that support `UCI_Elo`.
+    pub fn set_elo(&mut self, elo: u32) -> io::Result<()> {
+        self.set_option("UCI_LimitStrength", "true")?;
+        self.set_option("UCI_Elo", &elo.to_string())
+    }
+
+    /// Search the current position with `go` and return the best move.
+    pub fn best_move(&mut self, go: &str) -> io::Result<String> {
+        self.uci_send(go)?;
+        loop {
+            let line = self.read_line();
+            if let Some(rest) = line.strip_prefix("bestmove") {
+                return Ok(rest
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string());
+            }
+            if line.is_empty() && self.child.try_wait()?.is_some() {
+                return Err(io::Error::other("engine exited"));
+            }
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        self.uci_send("quit").ok();
+        if !matches!(self.child.try_wait(), Ok(Some(_))) {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            if !matches!(self.child.try_wait(), Ok(Some(_))) {
+                self.child.kill().ok();
+            }
+        }
+        self.child.wait().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_with_wdl_and_stats() {
+        let info = parse_info(
+            "info depth 20 seldepth 28 multipv 2 score cp -35 wdl 40 900 60 nodes 123456 \
+             nps 800000 hashfull 12 tbhits 0 time 154 pv e7e5 g1f3 b8c6",
+        )
+        .unwrap();
+        assert_eq!(info.multipv, 2);
+        assert_eq!((info.depth, info.seldepth), (Some(20), Some(28)));
+        assert_eq!(
+            (info.nodes, info.nps, info.time),
+            (Some(123456), Some(800000), Some(154))
+        );
+        assert_eq!(info.hashfull, Some(12));
+        assert_eq!(
+            info.wdl,
+            Some(Wdl {
+                win: 40,
+                draw: 900,
+                loss: 60
+            })
+        );
+        let line = info.line().unwrap();
+        assert!(matches!(line.eval_kind, EvalKind::Centipawn));
+        assert_eq!(
+            (line.eval_value, line.moves.as_str()),
+            (-35, "e7e5 g1f3 b8c6")
+        );
+
+        let mut black = info.clone();
+        black.normalize(-1);
+        assert_eq!(black.eval_value, Some(35));
+        assert_eq!(black.wdl.map(|wdl| (wdl.win, wdl.loss)), Some((60, 40)));
+    }
+
+    #[test]
+    fn bound_scores_have_no_line() {
+        let mut info =
+            parse_info("info depth 18 score cp 41 lowerbound nodes 900 pv e2e4").unwrap();
+        assert!(info.lowerbound && !info.upperbound);
+        assert_eq!((info.eval_value, info.nodes), (Some(41), Some(900)));
+        assert!(info.line().is_none());
+        assert!(parse_pv_line("info depth 18 score mate 3 upperbound pv e2e4").is_none());
+
+        info.normalize(-1);
+        assert!(info.upperbound && !info.lowerbound);
+    }
+
+    fn parse_pv_line(line: &str) -> Option<PvLineData> {
+        parse_info(line)?.line()
+    }
+
+    #[test]
+    fn strings_and_other_output() {
+        assert!(parse_info("info string NNUE evaluation using nn.nnue").is_none());
+        assert!(parse_info("bestmove e2e4 ponder e7e5").is_none());
+        let info = parse_info("info depth 5 currmove g1f3 currmovenumber 2 string hi").unwrap();
+        assert_eq!(info.currmove.as_deref(), Some("g1f3"));
+        assert_eq!(info.currmovenumber, Some(2));
+        assert!(info.line().is_none());
+    }
+
+    #[test]
+    fn option_lines() {
+        let option = |line: &str| parse_option_line(line).unwrap().kind;
+        assert_eq!(
+            option("option name Threads type spin default 1 min 1 max 1024"),
+            UciOptionKind::Spin {
+                default: 1,
+                min: 1,
+                max: 1024
+            }
+        );
+        assert_eq!(
+            option("option name Ponder type check default false"),
+            UciOptionKind::Check { default: false }
+        );
+        assert_eq!(
+            option("option name Style type combo default Normal var Solid var Normal var Risky"),
+            UciOptionKind::Combo {
+                default: "Normal".to_string(),
+                vars: vec!["Solid".into(), "Normal".into(), "Risky".into()]
+            }
+        );
+        assert_eq!(
+            option("option name SyzygyPath type string default <empty>"),
+            UciOptionKind::String {
+                default: String::new()
+            }
+        );
+        assert_eq!(
+            option("option name Clear Hash type button"),
+            UciOptionKind::Button
+        );
+
+        let spaced = parse_option_line("option name Skill Level type spin default 20 min 0 max 20");
+        assert_eq!(spaced.unwrap().name, "Skill Level");
+        assert!(parse_option_line("id name Stockfish 17").is_none());
+        assert!(parse_option_line("option name Odd type slider default 3").is_none());
+    }
+}
@@ -0,0 +1,230 @@
+use std::sync::Mutex;
+
+use crate::{
+    analyzer::analyzer::AnalyzerController,
+    database::create::update_game_pgn,
+    engine::{
+        move_gen::MoveError,
+        serializer::{serialize_analyzer_controller, SerializedAnalyzerController},
+        PieceType,
+    },
+    server::server::ServerState,
+};
+
+impl AnalyzerController {
+    /// Put the board on `target`, undoing back to the common ancestor with the
+    /// current node and replaying forward from there.
+    pub fn goto_node(&mut self, target: usize) -> Result<(), MoveError> {
+        let tree = &self.board.meta_data.move_tree;
+        if tree.get(target).is_none() {
+            return Err(MoveError::IllegalMove);
+        }
+        let current_path = tree.path_to(self.current_node);
+        let target_path = tree.path_to(target);
+        let common = current_path
+            .iter()
+            .zip(&target_path)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let forward: Vec<String> = target_path[common..]
+            .iter()
+            .filter_map(|id| tree.get(*id).and_then(|node| node.mv.as_ref()))
+            .map(|mv| mv.uci.clone())
+            .collect();
+
+        let mut board = self.board.clone();
+        let mut board_undo = self.board_undo.clone();
+        for _ in common..current_path.len() {
+            let undo = board_undo.pop().ok_or(MoveError::NoAviailableMoves)?;
+            board.apply_undo(undo)?;
+        }
+        for uci in forward {
+            let (from, to, promotion) = board
+                .decode_uci_move(&uci)
+                .ok_or(MoveError::IllegalMove)?;
+            board_undo.push(board.move_piece_with_undo(from, to, promotion)?);
+        }
+        board.rerender_move_cache();
+
+        self.board = board;
+        self.board_undo = board_undo;
+        self.current_node = target;
+        self.current_ply = target_path.len() as i32 - 1;
+        Ok(())
+    }
+
+    /// Keep the flat move list in step with the main line of the tree.
+    pub fn sync_move_list(&mut self) {
+        self.board.meta_data.move_list = self.board.meta_data.move_tree.mainline_moves();
+    }
+
+    /// Play a move from the current node. If the node already has children the
+    /// move becomes a new variation, otherwise it extends the line.
+    pub fn play_move(
+        &mut self,
+        from: (u8, u8),
+        to: (u8, u8),
+        promotion: Option<PieceType>,
+    ) -> Result<(), MoveError> {
+        let mut scratch = self.board.clone();
+        let mut mv = scratch.move_piece(from, to, promotion)?;
+        mv.san.push_str(scratch.san_check_suffix());
+        mv.move_number = (self.current_ply + 2) as u32;
+        let fen = scratch.to_string();
+
+        let id = self
+            .board
+            .meta_data
+            .move_tree
+            .add_move(self.current_node, mv, fen)
+            .ok_or(MoveError::IllegalMove)?;
+        self.sync_move_list();
+        self.goto_node(id)
+    }
+}
+
+fn tree_error(e: MoveError) -> String {
+    format!("{:?}", e)
+}
+
+#[tauri::command]
+pub fn play_analyzer_move(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    src_square: (u8, u8),
+    dest_square: (u8, u8),
+    promotion: Option<PieceType>,
+) -> Result<SerializedAnalyzerController, String> {
+    let mut state = state.lock().unwrap();
+    state
+        .analyzer_controller
+        .play_move(src_square, dest_square, promotion)
+        .map_err(tree_error)?;
+    Ok(serialize_analyzer_controller(&state.analyzer_controller))
+}
+
+#[tauri::command]
+pub fn goto_analyzer_node(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    node_id: usize,
+) -> Result<SerializedAnalyzerController, String> {
+    let mut state = state.lock().unwrap();
+    state
+        .analyzer_controller
+        .goto_node(node_id)
+        .map_err(tree_error)?;
+    Ok(serialize_analyzer_controller(&state.analyzer_controller))
+}
+
+/// Jump to the previous (-1) or next (1) alternative of the current move.
+#[tauri::command]
+pub fn goto_sibling_variation(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    offset: isize,
+) -> Result<SerializedAnalyzerController, String> {
+    let mut state = state.lock().unwrap();
+    let analyzer = &mut state.analyzer_controller;
+    let sibling = analyzer
+        .board
+        .meta_data
+        .move_tree
+        .sibling(analyzer.current_node, offset)
+        .ok_or("No variation in that direction")?;
+    analyzer.goto_node(sibling).map_err(tree_error)?;
+    Ok(serialize_analyzer_controller(analyzer))
+}
+
+fn shift_variation(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    node_id: usize,
+    offset: isize,
+) -> Result<SerializedAnalyzerController, String> {
+    let mut state = state.lock().unwrap();
+    let analyzer = &mut state.analyzer_controller;
+    if !analyzer
+        .board
+        .meta_data
+        .move_tree
+        .shift_variation(node_id, offset)
+    {
+        return Err("Variation can't be moved further".to_string());
+    }
+    analyzer.sync_move_list();
+    Ok(serialize_analyzer_controller(analyzer))
+}
+
+/// Move a variation one place up; promoting the first variation makes it the main line.
+#[tauri::command]
+pub fn promote_variation(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    node_id: usize,
+) -> Result<SerializedAnalyzerController, String> {
+    shift_variation(state, node_id, -1)
+}
+
+#[tauri::command]
+pub fn demote_variation(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    node_id: usize,
+) -> Result<SerializedAnalyzerController, String> {
+    shift_variation(state, node_id, 1)
+}
+
+/// Delete a move and everything after it. If the board is inside the deleted
+/// part it moves back to the move before.
+#[tauri::command]
+pub fn delete_variation(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    node_id: usize,
+) -> Result<SerializedAnalyzerController, String> {
+    let mut state = state.lock().unwrap();
+    let analyzer = &mut state.analyzer_controller;
+    let tree = &analyzer.board.meta_data.move_tree;
+    let parent = tree
+        .get(node_id)
+        .and_then(|node| node.parent)
+        .ok_or("The starting position can't be deleted")?;
+    let board_inside = tree.is_ancestor(node_id, analyzer.current_node);
+    if board_inside {
+        analyzer.goto_node(parent).map_err(tree_error)?;
+    }
+    analyzer.board.meta_data.move_tree.remove_subtree(node_id);
+    analyzer.sync_move_list();
+    Ok(serialize_analyzer_controller(analyzer))
+}
+
+#[tauri::command]
+pub fn annotate_analyzer_node(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    node_id: usize,
+    comments: Vec<String>,
+    nags: Vec<u8>,
+) -> Result<SerializedAnalyzerController, String> {
+    let mut state = state.lock().unwrap();
+    let analyzer = &mut state.analyzer_controller;
+    let node = analyzer
+        .board
+        .meta_data
+        .move_tree
+        .get_mut(node_id)
+        .ok_or("Unknown node")?;
+    node.comments = comments
+        .into_iter()
+        .filter(|comment| !comment.trim().is_empty())
+        .collect();
+    node.nags = nags;
+    analyzer.sync_move_list();
+    Ok(serialize_analyzer_controller(analyzer))
+}
+
+/// Write the analyzed game, variations included, back to its database row.
+#[tauri::command]
+pub fn save_analyzer_game(state: tauri::State<'_, Mutex<ServerState>>) -> Result<(), String> {
+    let state = state.lock().unwrap();
+    let analyzer = &state.analyzer_controller;
+    if analyzer.game_id == 0 {
+        return Err("This board is not a stored game".to_string());
+    }
+    let mut con = state.database.connection().map_err(|e| e.to_string())?;
+    update_game_pgn(&mut con, analyzer.game_id, &analyzer.board.meta_data)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,258 @@
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::database::db::state_connection;
+use crate::server::server::ServerState;
+
+/// A user-defined group of games, e.g. "Rook endgames".
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct Collection {
+    pub collection_id: u32,
+    pub name: String,
+    pub description: Option<String>,
+    pub game_count: u32,
+}
+
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct TagCount {
+    pub tag: String,
+    pub game_count: u32,
+}
+
+fn clean_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Name can't be empty".to_string());
+    }
+    Ok(name.to_string())
+}
+
+pub fn list_collections(con: &Connection) -> rusqlite::Result<Vec<Collection>> {
+    let mut stmt = con.prepare(
+        "SELECT c.collection_id, c.name, c.description, COUNT(cg.game_id)
+         FROM collections c LEFT JOIN collection_games cg ON cg.collection_id = c.collection_id
+         GROUP BY c.collection_id
+         ORDER BY c.name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Collection {
+            collection_id: row.get::<_, i64>(0)? as u32,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            game_count: row.get::<_, i64>(3)? as u32,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn collection_name(con: &Connection, collection_id: u32) -> rusqlite::Result<Option<String>> {
+    con.query_row(
+        "SELECT name FROM collections WHERE collection_id = ?1",
+        [collection_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+#[tauri::command]
+pub fn get_collections(
+    state: tauri::State<'_, Mutex<ServerState>>,
+) -> Result<Vec<Collection>, String> {
+    let con = state_connection(&state)?;
+    list_collections(&con).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_collection(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    name: String,
+    description: Option<String>,
+) -> Result<Collection, String> {
+    let name = clean_name(&name)?;
+    let con = state_connection(&state)?;
+    con.execute(
+        "INSERT INTO collections (name, description, created_at) VALUES (?1, ?2, datetime('now'))",
+        params![name, description],
+    )
+    .map_err(|e| format!("Could not create collection \"{}\": {}", name, e))?;
+    Ok(Collection {
+        collection_id: con.last_insert_rowid() as u32,
+        name,
+        description,
+        game_count: 0,
+    })
+}
+
+#[tauri::command]
+pub fn update_collection(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    collection_id: u32,
+    name: String,
+    description: Option<String>,
+) -> Result<(), String> {
+    let name = clean_name(&name)?;
+    let con = state_connection(&state)?;
+    let updated = con
+        .execute(
+            "UPDATE collections SET name = ?1, description = ?2 WHERE collection_id = ?3",
+            params![name, description, collection_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("Unknown collection".to_string());
+    }
+    Ok(())
+}
+
+/// Remove a collection. The games in it are kept.
+#[tauri::command]
+pub fn delete_collection(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    collection_id: u32,
+) -> Result<(), String> {
+    let mut con = state_connection(&state)?;
+    let tx = con.transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM collection_games WHERE collection_id = ?1",
+        [collection_id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM collections WHERE collection_id = ?1",
+        [collection_id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_games_to_collection(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    collection_id: u32,
+    game_ids: Vec<u32>,
+) -> Result<(), String> {
+    let mut con = state_connection(&state)?;
+    let tx = con.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT OR IGNORE INTO collection_games (collection_id, game_id, added_at)
+                 VALUES (?1, ?2, datetime('now'))",
+            )
+            .map_err(|e| e.to_string())?;
+        for game_id in game_ids {
+            stmt.execute(params![collection_id, game_id])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn remove_games_from_collection(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    collection_id: u32,
+    game_ids: Vec<u32>,
+) -> Result<(), String> {
+    let mut con = state_connection(&state)?;
+    let tx = con.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx
+            .prepare("DELETE FROM collection_games WHERE collection_id = ?1 AND game_id = ?2")
+            .map_err(|e| e.to_string())?;
+        for game_id in game_ids {
+            stmt.execute(params![collection_id, game_id])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Every tag in use, most used first.
+#[tauri::command]
+pub fn get_all_tags(state: tauri::State<'_, Mutex<ServerState>>) -> Result<Vec<TagCount>, String> {
+    let con = state_connection(&state)?;
+    let mut stmt = con
+        .prepare("SELECT tag, COUNT(*) FROM game_tags GROUP BY tag ORDER BY COUNT(*) DESC, tag")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(TagCount {
+                tag: row.get(0)?,
+                game_count: row.get::<_, i64>(1)? as u32,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<rusqlite::Result<_>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_game_tags(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    game_id: u32,
+) -> Result<Vec<String>, String> {
+    let con = state_connection(&state)?;
+    let mut stmt = con
+        .prepare("SELECT tag FROM game_tags WHERE game_id = ?1 ORDER BY tag")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([game_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<rusqlite::Result<_>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_game_tag(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    game_id: u32,
+    tag: String,
+) -> Result<(), String> {
+    let tag = clean_name(&tag)?;
+    let con = state_connection(&state)?;
+    con.execute(
+        "INSERT OR IGNORE INTO game_tags (game_id, tag) VALUES (?1, ?2)",
+        params![game_id, tag],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_game_tag(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    game_id: u32,
+    tag: String,
+) -> Result<(), String> {
+    let con = state_connection(&state)?;
+    con.execute(
+        "DELETE FROM game_tags WHERE game_id = ?1 AND tag = ?2",
+        params![game_id, tag.trim()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_favorite(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    game_id: u32,
+    favorite: bool,
+) -> Result<(), String> {
+    let con = state_connection(&state)?;
+    let result = if favorite {
+        con.execute(
+            "INSERT OR IGNORE INTO favorites (game_id, added_at) VALUES (?1, datetime('now'))",
+            [game_id],
+        )
+    } else {
+        con.execute("DELETE FROM favorites WHERE game_id = ?1", [game_id])
+    };
+    result.map(|_| ()).map_err(|e| e.to_string())
+}
@@ -0,0 +1,342 @@
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rusqlite::Connection;
+
+use crate::database::migrations::run_migrations;
+use crate::server::server::{ServerState, Settings};
+
+// settings key holding the database file, empty means the app-data default
+pub const DATABASE_PATH_SETTING: &str = "DatabasePath";
+const DATABASE_FILE: &str = "chess.db";
+// idle connections kept around for reuse
+const MAX_IDLE_CONNECTIONS: usize = 4;
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+static MEMORY_DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+/// `chess.db` in the platform data directory: `$XDG_DATA_HOME/koch` (or
+/// `~/.local/share/koch`), `~/Library/Application Support/koch` or `%APPDATA%\koch`.
+/// Falls back to the working directory when no home directory is known.
+pub fn default_database_path() -> PathBuf {
+    let env_dir = |key: &str| {
+        std::env::var_os(key)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    let data_dir = if cfg!(target_os = "windows") {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_DATA_HOME")
+            .or_else(|| env_dir("HOME").map(|home| home.join(".local").join("share")))
+    };
+    match data_dir {
+        Some(dir) => dir.join("koch").join(DATABASE_FILE),
+        None => PathBuf::from(DATABASE_FILE),
+    }
+}
+
+struct Inner {
+    // file path, or the shared-cache URI of an in-memory database
+    target: String,
+    path: Option<PathBuf>,
+    idle: Mutex<Vec<Connection>>,
+    // an in-memory database lives as long as one connection to it is open
+    _keep_alive: Option<Mutex<Connection>>,
+}
+
+/// Handle to the games database. Cloning is cheap and every clone shares the
+/// same pool of connections, so it can be handed to background threads.
+#[derive(Clone)]
+pub struct Database {
+    inner: Arc<Inner>,
+}
+
+/// A pooled connection, returned to the pool when dropped.
+pub struct PooledConnection {
+    con: Option<Connection>,
+    database: Database,
+}
+
+impl Database {
+    /// Open (or create) the database file at `path` and bring its schema up to date.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .inspect_err(|e| eprintln!("[DB] could not create {}: {e}", dir.display()))
+                .ok();
+        }
+        let database = Database {
+            inner: Arc::new(Inner {
+                target: path.to_string_lossy().to_string(),
+                path: Some(path.clone()),
+                idle: Mutex::new(Vec::new()),
+                _keep_alive: None,
+            }),
+        };
+        let mut con = database.connection()?;
+        run_migrations(&mut con, Some(&path))?;
+        println!("[DB] using {}", path.display());
+        drop(con);
+        Ok(database)
+    }
+
+    /// Private database that only lives in memory, for tests and throwaway sessions.
+    pub fn in_memory() -> rusqlite::Result<Self> {
+        let n = MEMORY_DATABASES.fetch_add(1, Ordering::Relaxed);
+        let target = format!("file:koch_memory_{}?mode=memory&cache=shared", n);
+        let mut keep_alive = Connection::open(&target)?;
+        run_migrations(&mut keep_alive, None)?;
+        Ok(Database {
+            inner: Arc::new(Inner {
+                target,
+                path: None,
+                idle: Mutex::new(Vec::new()),
+                _keep_alive: Some(Mutex::new(keep_alive)),
+            }),
+        })
+    }
+
+    /// Open the database named by the `DatabasePath` setting, or the default one.
+    pub fn from_settings(settings: &Settings) -> rusqlite::Result<Self> {
+        match settings
+            .map
+            .get(DATABASE_PATH_SETTING)
+            .map(|path| path.trim())
+            .filter(|path| !path.is_empty())
+        {
+            Some(path) => Database::open(path),
+            None => {
+                let path = default_database_path();
+                adopt_legacy_database(&path);
+                Database::open(path)
+            }
+        }
+    }
+
+    /// None for in-memory databases.
+    pub fn path(&self) -> Option<&Path> {
+        self.inner.path.as_deref()
+    }
+
+    pub fn connection(&self) -> rusqlite::Result<PooledConnection> {
+        let idle = self.inner.idle.lock().unwrap().pop();
+        let con = match idle {
+            Some(con) => con,
+            None => {
+                let con = Connection::open(&self.inner.target)?;
+                con.busy_timeout(BUSY_TIMEOUT)?;
+                // so the REFERENCES clauses reject rows of missing games
+                con.pragma_update(None, "foreign_keys", true)?;
+                con
+            }
+        };
+        Ok(PooledConnection {
+            con: Some(con),
+            database: self.clone(),
+        })
+    }
+}
+
+// Databases used to be created as `chess.db` in the working directory. Copy
+// one over the first time the default location is used so no games are lost.
+fn adopt_legacy_database(path: &Path) {
+    let legacy = Path::new(DATABASE_FILE);
+    if path.exists() || !legacy.is_file() || path == legacy {
+        return;
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).ok();
+    }
+    match std::fs::copy(legacy, path) {
+        Ok(_) => println!("[DB] copied {} to {}", legacy.display(), path.display()),
+        Err(e) => eprintln!("[DB] could not copy {}: {e}", legacy.display()),
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.con.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.con.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(con) = self.con.take() {
+            let mut idle = self.database.inner.idle.lock().unwrap();
+            if idle.len() < MAX_IDLE_CONNECTIONS {
+                idle.push(con);
+            }
+        }
+    }
+}
+
+/// Connection to the database owned by `ServerState`. The state lock is only
+/// held while cloning the handle, so don't call this while holding it.
+pub fn state_connection(state: &Mutex<ServerState>) -> Result<PooledConnection, String> {
+    let database = state.lock().unwrap().database.clone();
+    database.connection().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_database_path(state: tauri::State<'_, Mutex<ServerState>>) -> Option<String> {
+    let state = state.lock().unwrap();
+    state
+        .database
+        .path()
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/// Switch to another database file (created if missing) and remember it in
+/// the settings. `None` goes back to the default location.
+#[tauri::command]
+pub fn set_database_path(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    path: Option<String>,
+) -> Result<String, String> {
+    let path = path
+        .map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty());
+    let database = match path.as_ref() {
+        Some(path) => Database::open(path),
+        None => Database::open(default_database_path()),
+    }
+    .map_err(|e| format!("Could not open database: {}", e))?;
+
+    let mut state = state.lock().unwrap();
+    state
+        .settings
+        .update(DATABASE_PATH_SETTING.to_string(), path.unwrap_or_default());
+    state
+        .settings
+        .save()
+        .inspect_err(|e| eprintln!("[DB] could not save settings: {e}"))
+        .ok();
+    let resolved = database
+        .path()
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_default();
+    state.database = database;
+    state.database_error = None;
+    Ok(resolved)
+}
+
+/// Why the database couldn't be opened at startup, None when it was. Games
+/// of a session with an error are kept in memory and lost on exit.
+#[tauri::command]
+pub fn get_database_error(state: tauri::State<'_, Mutex<ServerState>>) -> Option<String> {
+    state.lock().unwrap().database_error.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create::get_game_by_id;
+    use crate::database::duplicates::{import_game, ImportOutcome};
+    use crate::database::migrations::{latest_version, user_version};
+    use crate::engine::pgn::parse_pgn;
+
+    const GAME: &str = r#"[Event "Casual"]
+[Site "Berlin"]
+[Date "1852.??.??"]
+[White "Anderssen, Adolf"]
+[Black "Dufresne, Jean"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. b4 Bxb4 5. c3 Ba5 6. d4 exd4 7. O-O d3
+8. Qb3 Qf6 9. e5 Qg6 10. Re1 Nge7 11. Ba3 b5 12. Qxb5 Rb8 13. Qa4 Bb6
+14. Nbd2 Bb7 15. Ne4 Qf5 16. Bxd3 Qh5 17. Nf6+ gxf6 18. exf6 Rg8
+19. Rad1 Qxf3 20. Rxe7+ Nxe7 21. Qxd7+ Kxd7 22. Bf5+ Ke8 23. Bd7+ Kf8
+24. Bxe7# 1-0
+"#;
+
+    #[test]
+    fn in_memory_database_is_migrated() {
+        let database = Database::in_memory().unwrap();
+        let con = database.connection().unwrap();
+        assert_eq!(user_version(&con).unwrap(), latest_version());
+        assert!(database.path().is_none());
+    }
+
+    #[test]
+    fn in_memory_databases_are_separate() {
+        let first = Database::in_memory().unwrap();
+        let second = Database::in_memory().unwrap();
+        let metadata = parse_pgn(GAME).unwrap();
+        import_game(&first.connection().unwrap(), &metadata).unwrap();
+        let count = |database: &Database| -> i64 {
+            let con = database.connection().unwrap();
+            con.query_row("SELECT COUNT(*) FROM games", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!((count(&first), count(&second)), (1, 0));
+    }
+
+    #[test]
+    fn rows_of_missing_games_are_rejected() {
+        let database = Database::in_memory().unwrap();
+        let con = database.connection().unwrap();
+        let favorite = |game_id: u32| {
+            con.execute(
+                "INSERT OR IGNORE INTO favorites (game_id, added_at) VALUES (?1, datetime('now'))",
+                [game_id],
+            )
+        };
+        assert!(favorite(1).is_err());
+
+        let metadata = parse_pgn(GAME).unwrap();
+        let ImportOutcome::New(game_id) = import_game(&con, &metadata).unwrap() else {
+            panic!("first import is not new");
+        };
+        assert_eq!(favorite(game_id).unwrap(), 1);
+    }
+
+    #[test]
+    fn import_round_trip() {
+        let database = Database::in_memory().unwrap();
+        let con = database.connection().unwrap();
+        let metadata = parse_pgn(GAME).unwrap();
+        let ImportOutcome::New(game_id) = import_game(&con, &metadata).unwrap() else {
+            panic!("first import is not new");
+        };
+
+        let stored = get_game_by_id(&con, game_id as usize).unwrap();
+        assert_eq!(stored.white_player_name, "Anderssen, Adolf");
+        assert_eq!(stored.black_player_name, "Dufresne, Jean");
+        assert_eq!(stored.result.to_string(), metadata.result.to_string());
+        let moves = |metadata: &crate::engine::board::BoardMetaData| -> Vec<String> {
+            metadata.move_list.iter().map(|mv| mv.uci.clone()).collect()
+        };
+        assert_eq!(moves(&stored), moves(&metadata));
+        assert_eq!(stored.move_list.len(), 47);
+
+        let positions: i64 = con
+            .query_row(
+                "SELECT COUNT(*) FROM positions WHERE game_id = ?1",
+                [game_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(positions > 0);
+
+        // the stored game reads back as the same game
+        assert!(matches!(
+            import_game(&con, &stored).unwrap(),
+            ImportOutcome::Duplicate(id) if id == game_id
+        ));
+    }
+}
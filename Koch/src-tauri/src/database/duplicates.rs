@@ -0,0 +1,206 @@
+use std::collections::HashSet;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::database::create::{get_game_by_id, insert_game, metadata_to_pgn, update_game_row};
+use crate::database::query::played_on;
+use crate::engine::board::{BoardMetaData, GameResult};
+use crate::engine::fen::position_key;
+
+/// What happened to a game handed to `import_game`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub enum ImportOutcome {
+    New(u32),
+    // already stored, nothing to add
+    Duplicate(u32),
+    // already stored, missing tags or annotations were merged in
+    Updated(u32),
+}
+
+impl ImportOutcome {
+    pub fn game_id(&self) -> u32 {
+        match self {
+            ImportOutcome::New(id) | ImportOutcome::Duplicate(id) | ImportOutcome::Updated(id) => {
+                *id
+            }
+        }
+    }
+}
+
+/// Running totals of an import, so every importer reports the same numbers.
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct ImportCounts {
+    pub new: u32,
+    pub duplicates: u32,
+    pub updated: u32,
+    // games that could not be read
+    pub failed: u32,
+}
+
+impl ImportCounts {
+    pub fn add(&mut self, outcome: ImportOutcome) {
+        match outcome {
+            ImportOutcome::New(_) => self.new += 1,
+            ImportOutcome::Duplicate(_) => self.duplicates += 1,
+            ImportOutcome::Updated(_) => self.updated += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: &ImportCounts) {
+        self.new += other.new;
+        self.duplicates += other.duplicates;
+        self.updated += other.updated;
+        self.failed += other.failed;
+    }
+}
+
+fn normalize_player(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Identity of a game: players, day played, start position and main line.
+/// Tags like the event or ratings, comments and variations don't take part,
+/// so the same game from two sources gets the same fingerprint.
+pub fn game_fingerprint(metadata: &BoardMetaData) -> String {
+    let mut canonical = format!(
+        "{}|{}|{}|{}|",
+        normalize_player(&metadata.white_player_name),
+        normalize_player(&metadata.black_player_name),
+        played_on(&metadata.date).unwrap_or_default(),
+        position_key(&metadata.starting_position),
+    );
+    for mv in &metadata.move_list {
+        canonical.push_str(&mv.uci);
+        canonical.push(' ');
+    }
+    // two FNV-1a passes with different offsets, 128 bits is plenty for a library
+    let fnv = |offset: u64| {
+        canonical.bytes().fold(offset, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    };
+    format!(
+        "{:016x}{:016x}",
+        fnv(0xcbf29ce484222325),
+        fnv(0x84222325cbf29ce4)
+    )
+}
+
+pub fn set_fingerprint(
+    con: &Connection,
+    game_id: i64,
+    metadata: &BoardMetaData,
+) -> rusqlite::Result<()> {
+    con.execute(
+        "UPDATE games SET fingerprint = ?1 WHERE game_id = ?2",
+        params![game_fingerprint(metadata), game_id],
+    )?;
+    Ok(())
+}
+
+pub fn find_duplicate(con: &Connection, fingerprint: &str) -> rusqlite::Result<Option<i64>> {
+    con.query_row(
+        "SELECT game_id FROM games WHERE fingerprint = ?1",
+        [fingerprint],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn is_unknown(value: &Option<String>) -> bool {
+    value
+        .as_deref()
+        .map(|v| v.trim().is_empty() || v == "?")
+        .unwrap_or(true)
+}
+
+// comments, NAGs, clocks, evals and variations of a game
+fn annotation_count(metadata: &BoardMetaData) -> usize {
+    let tree_notes: usize = metadata
+        .move_tree
+        .nodes
+        .values()
+        .map(|node| 1 + node.comments.len() + node.pre_comments.len() + node.nags.len())
+        .sum();
+    let move_notes = metadata
+        .move_list
+        .iter()
+        .filter(|mv| mv.clock.is_some() || mv.eval.is_some())
+        .count();
+    tree_notes + move_notes
+}
+
+/// `stored` completed with whatever `incoming` knows and it doesn't: unknown
+/// tags, ratings, the result, and the moves if `incoming` is better annotated.
+/// None when nothing would change.
+pub fn merge_games(stored: &BoardMetaData, incoming: &BoardMetaData) -> Option<BoardMetaData> {
+    let mut merged = stored.clone();
+    let fields = [
+        (&mut merged.opening, &incoming.opening),
+        (&mut merged.event, &incoming.event),
+        (&mut merged.site, &incoming.site),
+        (&mut merged.round, &incoming.round),
+        (&mut merged.time_control, &incoming.time_control),
+        (&mut merged.end_time, &incoming.end_time),
+        (&mut merged.link, &incoming.link),
+        (&mut merged.eco, &incoming.eco),
+    ];
+    for (field, other) in fields {
+        if is_unknown(field) && !is_unknown(other) {
+            *field = other.clone();
+        }
+    }
+    if merged.white_player_elo == 0 {
+        merged.white_player_elo = incoming.white_player_elo;
+    }
+    if merged.black_player_elo == 0 {
+        merged.black_player_elo = incoming.black_player_elo;
+    }
+    if matches!(merged.result, GameResult::Unfinished) {
+        merged.result = incoming.result.clone();
+        merged.termination = incoming.termination.clone();
+    }
+    let known: HashSet<String> = merged
+        .extra_tags
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect();
+    for (name, value) in &incoming.extra_tags {
+        if !known.contains(name) {
+            merged.extra_tags.push((name.clone(), value.clone()));
+        }
+    }
+    if annotation_count(incoming) > annotation_count(&merged) {
+        merged.move_tree = incoming.move_tree.clone();
+        merged.move_list = incoming.move_list.clone();
+    }
+
+    if metadata_to_pgn(&merged) == metadata_to_pgn(stored) {
+        None
+    } else {
+        Some(merged)
+    }
+}
+
+/// Store a game unless it is already in the database. A duplicate is
+/// completed with the new tags and annotations instead of being inserted twice.
+pub fn import_game(con: &Connection, metadata: &BoardMetaData) -> rusqlite::Result<ImportOutcome> {
+    let Some(game_id) = find_duplicate(con, &game_fingerprint(metadata))? else {
+        return Ok(ImportOutcome::New(insert_game(con, metadata)? as u32));
+    };
+    let stored = get_game_by_id(con, game_id as usize)?;
+    match merge_games(&stored, metadata) {
+        Some(merged) => {
+            update_game_row(con, game_id, &merged)?;
+            Ok(ImportOutcome::Updated(game_id as u32))
+        }
+        None => Ok(ImportOutcome::Duplicate(game_id as u32)),
+    }
+}
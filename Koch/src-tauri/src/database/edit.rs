@@ -0,0 +1,309 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+
+use crate::database::create::{metadata_to_pgn, update_game_row};
+use crate::database::db::state_connection;
+use crate::database::query::{filtered_game_ids, GameFilter};
+use crate::engine::board::BoardMetaData;
+use crate::engine::pgn::{parse_pgn, set_tag};
+use crate::server::server::ServerState;
+
+// how long the last edit or delete can be reverted
+pub const UNDO_WINDOW: Duration = Duration::from_secs(60);
+
+// Every table holding rows of a game, parents first, with the condition
+// selecting the rows of game `?1`.
+const GAME_TABLES: [(&str, &str); 9] = [
+    ("games", "game_id = ?1"),
+    ("rating_history", "game_id = ?1"),
+    ("positions", "game_id = ?1"),
+    ("game_analysis", "game_id = ?1"),
+    ("collection_games", "game_id = ?1"),
+    ("game_tags", "game_id = ?1"),
+    ("favorites", "game_id = ?1"),
+    ("chats", "game_id = ?1"),
+    (
+        "messages",
+        "chat_id IN (SELECT chat_id FROM chats WHERE game_id = ?1)",
+    ),
+];
+
+struct TableRows {
+    table: &'static str,
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+/// Copy of every row touched by the last edit or delete, kept in memory so
+/// the change can be reverted for `UNDO_WINDOW`.
+pub struct GameUndo {
+    pub description: String,
+    taken_at: Instant,
+    game_ids: Vec<i64>,
+    tables: Vec<TableRows>,
+}
+
+impl GameUndo {
+    pub fn expired(&self) -> bool {
+        self.taken_at.elapsed() > UNDO_WINDOW
+    }
+}
+
+fn snapshot(con: &Connection, game_ids: &[i64], description: String) -> rusqlite::Result<GameUndo> {
+    let mut tables = Vec::new();
+    for (table, condition) in GAME_TABLES {
+        let mut stmt = con.prepare(&format!("SELECT * FROM {} WHERE {}", table, condition))?;
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let mut rows = Vec::new();
+        for game_id in game_ids {
+            let game_rows = stmt.query_map([game_id], |row| {
+                (0..columns.len())
+                    .map(|i| row.get::<_, Value>(i))
+                    .collect::<rusqlite::Result<Vec<Value>>>()
+            })?;
+            for row in game_rows {
+                rows.push(row?);
+            }
+        }
+        tables.push(TableRows {
+            table,
+            columns,
+            rows,
+        });
+    }
+    Ok(GameUndo {
+        description,
+        taken_at: Instant::now(),
+        game_ids: game_ids.to_vec(),
+        tables,
+    })
+}
+
+fn delete_game_rows(con: &Connection, game_ids: &[i64]) -> rusqlite::Result<()> {
+    for (table, condition) in GAME_TABLES.iter().rev() {
+        let mut stmt = con.prepare(&format!("DELETE FROM {} WHERE {}", table, condition))?;
+        for game_id in game_ids {
+            stmt.execute([game_id])?;
+        }
+    }
+    Ok(())
+}
+
+fn restore(con: &Connection, undo: &GameUndo) -> rusqlite::Result<()> {
+    delete_game_rows(con, &undo.game_ids)?;
+    for table in &undo.tables {
+        let placeholders = vec!["?"; table.columns.len()].join(", ");
+        let mut stmt = con.prepare(&format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table.table,
+            table.columns.join(", "),
+            placeholders
+        ))?;
+        for row in &table.rows {
+            stmt.execute(params_from_iter(row.iter()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace a stored game (tags and moves) with `metadata` and return the undo record.
+pub fn edit_game(
+    con: &mut Connection,
+    game_id: i64,
+    metadata: &BoardMetaData,
+) -> rusqlite::Result<GameUndo> {
+    let tx = con.transaction()?;
+    let undo = snapshot(&tx, &[game_id], format!("edit game {}", game_id))?;
+    update_game_row(&tx, game_id, metadata)?;
+    tx.commit()?;
+    Ok(undo)
+}
+
+/// Delete games together with every row linked to them (positions, chats,
+/// messages, collection entries, tags and favorites).
+pub fn delete_game_ids(con: &mut Connection, game_ids: &[i64]) -> rusqlite::Result<GameUndo> {
+    let tx = con.transaction()?;
+    let description = match game_ids {
+        [game_id] => format!("delete game {}", game_id),
+        _ => format!("delete {} games", game_ids.len()),
+    };
+    let undo = snapshot(&tx, game_ids, description)?;
+    delete_game_rows(&tx, game_ids)?;
+    tx.commit()?;
+    Ok(undo)
+}
+
+pub fn undo_change(con: &mut Connection, undo: &GameUndo) -> rusqlite::Result<()> {
+    let tx = con.transaction()?;
+    restore(&tx, undo)?;
+    tx.commit()
+}
+
+fn load_game(con: &Connection, game_id: i64) -> Result<BoardMetaData, String> {
+    let pgn: String = con
+        .query_row(
+            "SELECT pgn_data FROM games WHERE game_id = ?1",
+            [game_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    parse_pgn(&pgn).map_err(|e| e.to_string())
+}
+
+// Round-trip edited metadata through PGN, so the move list, the FENs and the
+// fingerprint all come from the moves that are stored, and illegal moves are
+// rejected instead of saved.
+fn reparse(metadata: &BoardMetaData) -> Result<BoardMetaData, String> {
+    parse_pgn(&metadata_to_pgn(metadata)).map_err(|e| format!("Invalid game: {}", e))
+}
+
+/// Overwrite a game with edited metadata, moves included.
+#[tauri::command]
+pub fn update_game(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    game_id: u32,
+    metadata: BoardMetaData,
+) -> Result<(), String> {
+    let metadata = reparse(&metadata)?;
+    let mut con = state_connection(&state)?;
+    let undo = edit_game(&mut con, game_id as i64, &metadata).map_err(|e| e.to_string())?;
+    state.lock().unwrap().game_undo = Some(undo);
+    Ok(())
+}
+
+/// Set PGN tags of a stored game, e.g. `("White", "Carlsen, Magnus")`.
+/// An empty value removes a tag that has no dedicated field.
+#[tauri::command]
+pub fn update_game_tags(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    game_id: u32,
+    tags: Vec<(String, String)>,
+) -> Result<(), String> {
+    let mut con = state_connection(&state)?;
+    let mut metadata = load_game(&con, game_id as i64)?;
+    for (tag, value) in tags {
+        // written from the starting position, which the moves depend on
+        if tag == "FEN" || tag == "SetUp" {
+            continue;
+        }
+        if set_tag(&mut metadata, &tag, &value) {
+            continue;
+        }
+        metadata.extra_tags.retain(|(name, _)| *name != tag);
+        if !value.is_empty() {
+            metadata.extra_tags.push((tag, value));
+        }
+    }
+    let undo = edit_game(&mut con, game_id as i64, &metadata).map_err(|e| e.to_string())?;
+    state.lock().unwrap().game_undo = Some(undo);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_game(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    game_id: u32,
+) -> Result<(), String> {
+    let mut con = state_connection(&state)?;
+    let undo = delete_game_ids(&mut con, &[game_id as i64]).map_err(|e| e.to_string())?;
+    state.lock().unwrap().game_undo = Some(undo);
+    Ok(())
+}
+
+/// Delete every game matching `filter` and return how many were removed.
+#[tauri::command]
+pub fn delete_games(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    filter: GameFilter,
+) -> Result<u32, String> {
+    filter.validate()?;
+    let mut con = state_connection(&state)?;
+    let game_ids = filtered_game_ids(&con, &filter).map_err(|e| e.to_string())?;
+    if game_ids.is_empty() {
+        return Ok(0);
+    }
+    let undo = delete_game_ids(&mut con, &game_ids).map_err(|e| e.to_string())?;
+    println!("[DB] {}", undo.description);
+    state.lock().unwrap().game_undo = Some(undo);
+    Ok(game_ids.len() as u32)
+}
+
+/// Revert the last edit or delete if it happened within `UNDO_WINDOW`.
+/// Returns the ids of the restored games. A failed undo can be retried.
+#[tauri::command]
+pub fn undo_game_change(state: tauri::State<'_, Mutex<ServerState>>) -> Result<Vec<u32>, String> {
+    let (database, undo) = {
+        let mut state = state.lock().unwrap();
+        let undo = state
+            .game_undo
+            .take()
+            .filter(|undo| !undo.expired())
+            .ok_or("Nothing to undo")?;
+        (state.database.clone(), undo)
+    };
+    let restored = database
+        .connection()
+        .and_then(|mut con| undo_change(&mut con, &undo));
+    if let Err(e) = restored {
+        eprintln!("[DB] could not undo {}: {}", undo.description, e);
+        // keep it, unless a newer change replaced it meanwhile
+        let mut state = state.lock().unwrap();
+        if state.game_undo.is_none() {
+            state.game_undo = Some(undo);
+        }
+        return Err(e.to_string());
+    }
+    println!("[DB] undid {}", undo.description);
+    Ok(undo.game_ids.iter().map(|id| *id as u32).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::db::Database;
+    use crate::database::duplicates::{import_game, ImportOutcome};
+
+    #[test]
+    fn undo_restores_the_rating_history() {
+        let database = Database::in_memory().unwrap();
+        let mut con = database.connection().unwrap();
+        let metadata = parse_pgn("[White \"a\"]\n[Black \"b\"]\n\n1. e4 e5 1-0").unwrap();
+        let ImportOutcome::New(game_id) = import_game(&con, &metadata).unwrap() else {
+            panic!("first import is not new");
+        };
+        con.execute(
+            "INSERT INTO rating_history (time_class, game_id, rating_before, rating_after,
+                opponent_rating, score)
+             VALUES ('blitz', ?1, 1500, 1510, 1500, 1.0)",
+            [game_id],
+        )
+        .unwrap();
+        let history = |con: &Connection| -> i64 {
+            con.query_row(
+                "SELECT COUNT(*) FROM rating_history WHERE game_id = ?1",
+                [game_id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        let undo = delete_game_ids(&mut con, &[game_id as i64]).unwrap();
+        assert_eq!(history(&con), 0);
+        undo_change(&mut con, &undo).unwrap();
+        assert_eq!(history(&con), 1);
+    }
+
+    #[test]
+    fn edits_with_illegal_moves_are_rejected() {
+        let mut metadata = parse_pgn("[White \"a\"]\n[Black \"b\"]\n\n1. e4 e5 1-0").unwrap();
+        assert_eq!(reparse(&metadata).unwrap().move_list.len(), 2);
+
+        let last = *metadata.move_tree.mainline().last().unwrap();
+        let node = metadata.move_tree.get_mut(last).unwrap();
+        node.mv.as_mut().unwrap().san = "Ke7".to_string();
+        assert!(reparse(&metadata).is_err());
+    }
+}
@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::analyzer::analyzer::EngineCommand;
+use crate::analyzer::uci_engine::{UciEngine, UciOption, UciOptionKind};
+use crate::database::db::{state_connection, Database};
+use crate::server::server::{ServerState, Settings};
+
+// used until an engine is registered and picked; stored as engine 0 once its
+// options are known
+pub const DEFAULT_ENGINE_ID: u32 = 0;
+pub const DEFAULT_ENGINE_PATH: &str = "/usr/bin/stockfish";
+pub const ANALYZER_ENGINE_SETTING: &str = "AnalyzerEngine";
+pub const GAME_ENGINE_SETTING: &str = "GameEngine";
+
+/// What an engine is picked for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub enum EngineRole {
+    Analyzer,
+    Game,
+}
+
+impl EngineRole {
+    fn setting(&self) -> &'static str {
+        match self {
+            EngineRole::Analyzer => ANALYZER_ENGINE_SETTING,
+            EngineRole::Game => GAME_ENGINE_SETTING,
+        }
+    }
+}
+
+/// A registered UCI engine with the options it declared and the values set
+/// for them.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct EngineConfig {
+    // 0 for the default engine, which isn't registered
+    pub engine_id: u32,
+    pub name: String,
+    pub path: String,
+    pub args: Vec<String>,
+    pub options: Vec<UciOption>,
+    // option name -> value, only for options changed from their default
+    pub values: HashMap<String, String>,
+}
+
+impl EngineConfig {
+    pub fn default_engine() -> Self {
+        EngineConfig {
+            engine_id: DEFAULT_ENGINE_ID,
+            name: "Stockfish".to_string(),
+            path: DEFAULT_ENGINE_PATH.to_string(),
+            args: Vec::new(),
+            options: Vec::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    /// Start the engine with its stored option values. Returns the engine and
+    /// the "id name" it reported, e.g. "Stockfish 16.1".
+    pub fn launch(&self) -> Result<(UciEngine, String), String> {
+        let mut engine = UciEngine::spawn(&self.path, &self.args)
+            .map_err(|e| format!("could not start {}: {e}", self.path))?;
+        let (id_name, _) = engine.handshake().map_err(|e| e.to_string())?;
+        for (name, value) in &self.values {
+            if let Err(e) = engine.set_option(name, value) {
+                eprintln!("[Engine] could not set {} on {}: {e}", name, self.name);
+            }
+        }
+        Ok((engine, id_name))
+    }
+}
+
+fn json_column<T: serde::de::DeserializeOwned + Default>(
+    row: &Row,
+    index: usize,
+) -> rusqlite::Result<T> {
+    let text: String = row.get(index)?;
+    Ok(serde_json::from_str(&text).unwrap_or_default())
+}
+
+fn to_json<T: Serialize>(value: &T) -> rusqlite::Result<String> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn engine_row(row: &Row) -> rusqlite::Result<EngineConfig> {
+    Ok(EngineConfig {
+        engine_id: row.get::<_, i64>(0)? as u32,
+        name: row.get(1)?,
+        path: row.get(2)?,
+        args: json_column(row, 3)?,
+        options: json_column(row, 4)?,
+        values: json_column(row, 5)?,
+    })
+}
+
+/// Registered engines, the default engine first when it is stored.
+pub fn list_engines(con: &Connection) -> rusqlite::Result<Vec<EngineConfig>> {
+    let mut stmt = con.prepare(
+        "SELECT engine_id, name, path, args, options, option_values
+         FROM engines ORDER BY engine_id != 0, name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], engine_row)?;
+    rows.collect()
+}
+
+pub fn load_engine(con: &Connection, engine_id: u32) -> rusqlite::Result<Option<EngineConfig>> {
+    con.query_row(
+        "SELECT engine_id, name, path, args, options, option_values
+         FROM engines WHERE engine_id = ?1",
+        [engine_id],
+        engine_row,
+    )
+    .optional()
+}
+
+pub fn insert_engine(con: &Connection, engine: &EngineConfig) -> rusqlite::Result<u32> {
+    con.execute(
+        "INSERT INTO engines (name, path, args, options, option_values, added_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))",
+        params![
+            engine.name,
+            engine.path,
+            to_json(&engine.args)?,
+            to_json(&engine.options)?,
+            to_json(&engine.values)?
+        ],
+    )?;
+    Ok(con.last_insert_rowid() as u32)
+}
+
+/// Start the engine at `path` once and read its "id name" and options.
+pub fn probe_engine(path: &str, args: &[String]) -> Result<(String, Vec<UciOption>), String> {
+    let mut engine =
+        UciEngine::spawn(path, args).map_err(|e| format!("could not start {}: {e}", path))?;
+    engine.handshake().map_err(|e| format!("{}: {e}", path))
+}
+
+/// The default engine with its options, handshaking it and storing it as
+/// engine 0 the first time. Falls back to it without options when it can't
+/// be started. Blocks for up to `HANDSHAKE_TIMEOUT`.
+pub fn default_engine(database: &Database) -> Result<EngineConfig, String> {
+    let con = database.connection().map_err(|e| e.to_string())?;
+    if let Some(engine) = load_engine(&con, DEFAULT_ENGINE_ID).map_err(|e| e.to_string())? {
+        return Ok(engine);
+    }
+    drop(con);
+    let mut engine = EngineConfig::default_engine();
+    let options = match probe_engine(&engine.path, &engine.args) {
+        Ok((_, options)) => options,
+        Err(e) => {
+            eprintln!("[Engine] default engine unavailable: {e}");
+            return Ok(engine);
+        }
+    };
+    engine.options = options;
+    let con = database.connection().map_err(|e| e.to_string())?;
+    con.execute(
+        "INSERT OR IGNORE INTO engines
+            (engine_id, name, path, args, options, option_values, added_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'))",
+        params![
+            DEFAULT_ENGINE_ID,
+            engine.name,
+            engine.path,
+            to_json(&engine.args).map_err(|e| e.to_string())?,
+            to_json(&engine.options).map_err(|e| e.to_string())?,
+            to_json(&engine.values).map_err(|e| e.to_string())?
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(engine)
+}
+
+fn store_values(con: &Connection, engine: &EngineConfig) -> rusqlite::Result<()> {
+    con.execute(
+        "UPDATE engines SET option_values = ?1 WHERE engine_id = ?2",
+        params![to_json(&engine.values)?, engine.engine_id],
+    )?;
+    Ok(())
+}
+
+/// Engine picked for `role`, or the default engine when none is picked or the
+/// picked one was removed.
+pub fn engine_for(con: &Connection, settings: &Settings, role: EngineRole) -> EngineConfig {
+    let engine_id = settings
+        .map
+        .get(role.setting())
+        .and_then(|id| id.trim().parse::<u32>().ok())
+        .unwrap_or(DEFAULT_ENGINE_ID);
+    let engine = match load_engine(con, engine_id) {
+        Ok(None) if engine_id != DEFAULT_ENGINE_ID => load_engine(con, DEFAULT_ENGINE_ID),
+        loaded => loaded,
+    };
+    match engine {
+        Ok(Some(engine)) => engine,
+        Ok(None) => EngineConfig::default_engine(),
+        Err(e) => {
+            eprintln!("[DB] could not read engine {}: {e}", engine_id);
+            EngineConfig::default_engine()
+        }
+    }
+}
+
+/// The default engine followed by the registered ones. The default engine is
+/// started once to read its options the first time.
+#[tauri::command]
+pub async fn get_engines(
+    state: tauri::State<'_, Mutex<ServerState<'_>>>,
+) -> Result<Vec<EngineConfig>, String> {
+    let database = state.lock().map_err(|e| e.to_string())?.database.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let default = default_engine(&database)?;
+        let con = database.connection().map_err(|e| e.to_string())?;
+        let mut engines = list_engines(&con).map_err(|e| e.to_string())?;
+        if engines.first().map(|engine| engine.engine_id) != Some(DEFAULT_ENGINE_ID) {
+            engines.insert(0, default);
+        }
+        Ok(engines)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Start the engine at `path` once to read its options, then register it.
+/// An empty `name` takes the name the engine reports.
+#[tauri::command]
+pub async fn register_engine(
+    state: tauri::State<'_, Mutex<ServerState<'_>>>,
+    name: String,
+    path: String,
+    args: Vec<String>,
+) -> Result<EngineConfig, String> {
+    let path = path.trim().to_string();
+    if path.is_empty() {
+        return Err("Path can't be empty".to_string());
+    }
+    let (id_name, options) = {
+        let (path, args) = (path.clone(), args.clone());
+        tauri::async_runtime::spawn_blocking(move || probe_engine(&path, &args))
+            .await
+            .map_err(|e| e.to_string())??
+    };
+    let mut engine = EngineConfig {
+        engine_id: 0,
+        name: match name.trim() {
+            "" => id_name,
+            name => name.to_string(),
+        },
+        path,
+        args,
+        options,
+        values: HashMap::new(),
+    };
+    let con = state_connection(&state)?;
+    engine.engine_id = insert_engine(&con, &engine).map_err(|e| e.to_string())?;
+    println!(
+        "[Engine] registered {} ({} options)",
+        engine.name,
+        engine.options.len()
+    );
+    Ok(engine)
+}
+
+#[tauri::command]
+pub fn remove_engine(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    engine_id: u32,
+) -> Result<(), String> {
+    if engine_id == DEFAULT_ENGINE_ID {
+        return Err("The default engine can't be removed".to_string());
+    }
+    let con = state_connection(&state)?;
+    con.execute("DELETE FROM engines WHERE engine_id = ?1", [engine_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Set option `name` of an engine, or press it for a button. The value is
+/// kept for the next launches and applied to the running analyzer or game
+/// engine when it is this one.
+#[tauri::command]
+pub async fn set_uci_option(
+    state: tauri::State<'_, Mutex<ServerState<'_>>>,
+    engine_id: u32,
+    name: String,
+    value: String,
+) -> Result<EngineConfig, String> {
+    let database = state.lock().map_err(|e| e.to_string())?.database.clone();
+    let mut engine = match engine_id {
+        DEFAULT_ENGINE_ID => {
+            let database = database.clone();
+            tauri::async_runtime::spawn_blocking(move || default_engine(&database))
+                .await
+                .map_err(|e| e.to_string())??
+        }
+        _ => {
+            let con = database.connection().map_err(|e| e.to_string())?;
+            load_engine(&con, engine_id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| "Engine not found".to_string())?
+        }
+    };
+    let option = engine
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .ok_or_else(|| format!("{} has no option {}", engine.name, name))?;
+    let value = match option.kind {
+        UciOptionKind::Button => None,
+        _ => Some(option.check_value(&value)?),
+    };
+    if let Some(value) = &value {
+        engine.values.insert(name.clone(), value.clone());
+        let con = database.connection().map_err(|e| e.to_string())?;
+        store_values(&con, &engine).map_err(|e| e.to_string())?;
+    }
+
+    let mut state = state.lock().unwrap();
+    let picked = |role: EngineRole| {
+        let picked_id = state
+            .settings
+            .map
+            .get(role.setting())
+            .and_then(|id| id.trim().parse::<u32>().ok())
+            .unwrap_or(DEFAULT_ENGINE_ID);
+        picked_id == engine_id
+    };
+    let (for_analyzer, for_game) = (picked(EngineRole::Analyzer), picked(EngineRole::Game));
+    if for_analyzer {
+        if let Some(tx) = &state.analyzer_tx {
+            tx.send(EngineCommand::SetUciOption(name.clone(), value.clone()))
+                .inspect_err(|e| eprintln!("[Analyzer] SetUciOption send failed: {e}"))
+                .ok();
+        }
+    }
+    if let (true, Some(game_engine)) = (for_game, state.engine.as_mut()) {
+        let sent = match &value {
+            Some(value) => game_engine.set_option(&name, value),
+            None => game_engine.uci_send(&format!("setoption name {}", name)),
+        };
+        sent.inspect_err(|e| eprintln!("[Engine] could not set {}: {e}", name))
+            .ok();
+    }
+    Ok(engine)
+}
+
+/// Use engine `engine_id` for `role` from now on, the default engine when None.
+#[tauri::command]
+pub async fn select_engine(
+    state: tauri::State<'_, Mutex<ServerState<'_>>>,
+    role: EngineRole,
+    engine_id: Option<u32>,
+) -> Result<EngineConfig, String> {
+    let database = state.lock().map_err(|e| e.to_string())?.database.clone();
+    // start the game engine before taking the state lock, it takes a moment
+    let (engine, game_engine) = tauri::async_runtime::spawn_blocking(move || {
+        let engine = match engine_id {
+            Some(engine_id) => {
+                let con = database.connection().map_err(|e| e.to_string())?;
+                load_engine(&con, engine_id)
+                    .map_err(|e| e.to_string())?
+                    .ok_or_else(|| "Engine not found".to_string())?
+            }
+            None => default_engine(&database)?,
+        };
+        let game_engine = match role {
+            EngineRole::Game => Some(engine.launch()?.0),
+            EngineRole::Analyzer => None,
+        };
+        Ok::<_, String>((engine, game_engine))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let mut state = state.lock().unwrap();
+    state
+        .settings
+        .update(role.setting().to_string(), engine.engine_id.to_string());
+    if let Err(e) = state.settings.save() {
+        eprintln!("[Engine] Failed to save settings: {}", e);
+    }
+    match role {
+        EngineRole::Analyzer => {
+            let Some(tx) = &state.analyzer_tx else {
+                return Err("Analyzer is not running".to_string());
+            };
+            tx.send(EngineCommand::SetEngine(engine.clone()))
+                .map_err(|e| e.to_string())?;
+        }
+        EngineRole::Game => {
+            state.engine = game_engine;
+            if let Some(game_engine) = state.engine.as_mut() {
+                game_engine.setup_for_new_game().ok();
+            }
+            state.apply_ratings();
+        }
+    }
+    Ok(engine)
+}
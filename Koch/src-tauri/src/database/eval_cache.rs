@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::engine::fen::position_hash;
+use crate::server::server::{PvLineData, PvObject};
+
+/// Deepest stored analysis of `fen` by `engine` with at least `multipv` lines.
+/// Lines beyond `multipv` are dropped; `fen` of the result is left empty.
+pub fn lookup_eval(
+    con: &Connection,
+    fen: &str,
+    engine: &str,
+    multipv: u32,
+) -> rusqlite::Result<Option<PvObject>> {
+    let row: Option<(u32, String)> = con
+        .query_row(
+            "SELECT depth, lines FROM eval_cache
+             WHERE position_hash = ?1 AND engine = ?2 AND multipv >= ?3
+             ORDER BY depth DESC, multipv ASC
+             LIMIT 1",
+            params![position_hash(fen), engine, multipv],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some(mut pv) = row.and_then(cached_pv) else {
+        return Ok(None);
+    };
+    pv.lines.retain(|index, _| (*index as u32) <= multipv);
+    Ok(Some(pv))
+}
+
+fn cached_pv((depth, lines): (u32, String)) -> Option<PvObject> {
+    let lines: HashMap<u8, PvLineData> = match serde_json::from_str(&lines) {
+        Ok(lines) => lines,
+        Err(e) => {
+            eprintln!("[DB] ignoring unreadable cached eval: {e}");
+            return None;
+        }
+    };
+    Some(PvObject {
+        fen: String::new(),
+        depth,
+        lines,
+        san_lines: HashMap::new(),
+        line_info: HashMap::new(),
+        search_info: None,
+    })
+}
+
+/// Remember the lines of `pv` for `fen`, unless a deeper search is already stored.
+/// Evals are stored as in `PvObject`, from White's point of view.
+pub fn store_eval(
+    con: &Connection,
+    fen: &str,
+    engine: &str,
+    multipv: u32,
+    pv: &PvObject,
+) -> rusqlite::Result<()> {
+    if pv.lines.is_empty() {
+        return Ok(());
+    }
+    let lines = serde_json::to_string(&pv.lines)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    con.execute(
+        "INSERT INTO eval_cache (position_hash, engine, multipv, depth, lines, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))
+         ON CONFLICT(position_hash, engine, multipv) DO UPDATE SET
+            depth = excluded.depth,
+            lines = excluded.lines,
+            updated_at = excluded.updated_at
+         WHERE excluded.depth >= eval_cache.depth",
+        params![position_hash(fen), engine, multipv, pv.depth, lines],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::database::collections::collection_name;
+use crate::database::create::metadata_to_pgn_with_options;
+use crate::database::db::state_connection;
+use crate::database::query::GameFilter;
+use crate::engine::pgn::{parse_pgn, PgnExportOptions};
+use crate::server::server::ServerState;
+
+/// Which stored games an export covers.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub enum ExportSelection {
+    All,
+    Ids(Vec<u32>),
+    Filter(GameFilter),
+    // games of a collection, written with a `[Collection "<name>"]` tag
+    Collection(u32),
+}
+
+impl ExportSelection {
+    fn to_sql(&self) -> (String, Vec<Value>) {
+        match self {
+            ExportSelection::All => ("1 = 1".to_string(), Vec::new()),
+            ExportSelection::Ids(ids) => {
+                let placeholders = vec!["?"; ids.len()].join(", ");
+                (
+                    format!("game_id IN ({})", placeholders),
+                    ids.iter().map(|id| Value::Integer(*id as i64)).collect(),
+                )
+            }
+            ExportSelection::Filter(filter) => filter.to_sql(),
+            ExportSelection::Collection(collection_id) => (
+                "game_id IN (SELECT game_id FROM collection_games WHERE collection_id = ?)"
+                    .to_string(),
+                vec![Value::Integer(*collection_id as i64)],
+            ),
+        }
+    }
+}
+
+/// Write the selected games to `out` as PGN, ordered by game id.
+/// Returns the number of games written.
+pub fn write_games<W: Write>(
+    con: &Connection,
+    selection: &ExportSelection,
+    options: &PgnExportOptions,
+    out: &mut W,
+) -> Result<u32, Box<dyn Error>> {
+    if let ExportSelection::Ids(ids) = selection {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+    }
+    let collection = match selection {
+        ExportSelection::Collection(collection_id) => Some(
+            collection_name(con, *collection_id)?
+                .ok_or_else(|| format!("Unknown collection {}", collection_id))?,
+        ),
+        _ => None,
+    };
+    let (condition, values) = selection.to_sql();
+    let mut stmt = con.prepare(&format!(
+        "SELECT game_id, pgn_data FROM games WHERE {} ORDER BY game_id",
+        condition
+    ))?;
+    let mut rows = stmt.query(params_from_iter(values.iter()))?;
+
+    let mut written = 0;
+    while let Some(row) = rows.next()? {
+        let game_id: i64 = row.get(0)?;
+        let pgn_data: String = row.get(1)?;
+        let pgn = match parse_pgn(&pgn_data) {
+            Ok(mut metadata) => {
+                if let Some(name) = collection.as_ref() {
+                    metadata.extra_tags.retain(|(tag, _)| tag != "Collection");
+                    metadata
+                        .extra_tags
+                        .push(("Collection".to_string(), name.clone()));
+                }
+                metadata_to_pgn_with_options(&metadata, options)
+            }
+            Err(e) => {
+                // still export the game, just without applying the options
+                eprintln!(
+                    "[Export] game {} has invalid PGN, writing as stored: {}",
+                    game_id, e
+                );
+                format!("{}\n", pgn_data.trim())
+            }
+        };
+        if written > 0 {
+            out.write_all(b"\n")?;
+        }
+        out.write_all(pgn.as_bytes())?;
+        written += 1;
+    }
+    out.flush()?;
+    Ok(written)
+}
+
+#[tauri::command]
+pub fn export_games(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    path: String,
+    selection: ExportSelection,
+    options: PgnExportOptions,
+) -> Result<u32, String> {
+    if let ExportSelection::Filter(filter) = &selection {
+        filter.validate()?;
+    }
+    let con = state_connection(&state)?;
+    let file = File::create(&path).map_err(|e| format!("Could not create {}: {}", path, e))?;
+    let mut out = BufWriter::new(file);
+    let written = write_games(&con, &selection, &options, &mut out).map_err(|e| e.to_string())?;
+    println!("[Export] wrote {} games to {}", written, path);
+    Ok(written)
+}
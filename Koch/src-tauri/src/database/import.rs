@@ -0,0 +1,308 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use ts_rs::TS;
+
+use crate::database::db::Database;
+use crate::database::duplicates::{import_game, ImportCounts};
+use crate::database::lichess::{
+    parse_lichess_json, parse_lichess_pgn, NdjsonGameReader, DEFAULT_LICHESS_URL,
+};
+use crate::engine::board::BoardMetaData;
+use crate::server::server::ServerState;
+
+// games per transaction
+const IMPORT_BATCH_SIZE: usize = 500;
+// keep the report small when a file is mostly garbage
+const MAX_REPORTED_ERRORS: usize = 500;
+
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct ImportProgress {
+    pub processed: u32,
+    // new games; duplicates of stored games are counted separately
+    pub imported: u32,
+    pub duplicates: u32,
+    pub updated: u32,
+    pub failed: u32,
+    // 0.0 - 100.0, based on bytes read
+    pub percent: f32,
+}
+
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct ImportError {
+    // 1-based position of the game in the file
+    pub index: u32,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct ImportReport {
+    pub processed: u32,
+    pub imported: u32,
+    // already stored and left as is
+    pub duplicates: u32,
+    // already stored, completed with tags or annotations from the file
+    pub updated: u32,
+    pub failed: u32,
+    pub errors: Vec<ImportError>,
+    pub cancelled: bool,
+    // set when the import stopped on an IO or database error
+    pub error: Option<String>,
+}
+
+/// Reads a PGN file one game at a time, so large databases never have to be
+/// loaded into memory at once. Lines are decoded lossily since older PGN
+/// archives are frequently Latin-1.
+pub struct PgnGameReader<R: BufRead> {
+    reader: R,
+    buf: Vec<u8>,
+    // first tag line of the next game, read while looking for the end of the current one
+    pending: Option<String>,
+    pub bytes_read: u64,
+}
+
+impl<R: BufRead> PgnGameReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            pending: None,
+            bytes_read: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnGameReader<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut game = self.pending.take().unwrap_or_default();
+        let mut in_movetext = false;
+        let mut comment_depth: i32 = 0;
+
+        loop {
+            self.buf.clear();
+            let n = match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(n) => n,
+                Err(e) => return Some(Err(e)),
+            };
+            if n == 0 {
+                break;
+            }
+            self.bytes_read += n as u64;
+            let line = String::from_utf8_lossy(&self.buf);
+            let line = line.trim_start_matches('\u{feff}');
+            let trimmed = line.trim_start();
+
+            if comment_depth == 0 {
+                if trimmed.starts_with('[') {
+                    if in_movetext {
+                        self.pending = Some(line.to_string());
+                        break;
+                    }
+                } else if !trimmed.is_empty() && !trimmed.starts_with('%') {
+                    in_movetext = true;
+                }
+            }
+            for c in trimmed.chars() {
+                match c {
+                    '{' => comment_depth += 1,
+                    '}' => comment_depth = (comment_depth - 1).max(0),
+                    _ => {}
+                }
+            }
+            game.push_str(line);
+        }
+
+        if game.trim().is_empty() {
+            None
+        } else {
+            Some(Ok(game))
+        }
+    }
+}
+
+/// A file read one game at a time.
+trait GameSource: Iterator<Item = io::Result<String>> {
+    fn bytes_read(&self) -> u64;
+    fn parse(&self, game: &str) -> Result<BoardMetaData, String>;
+}
+
+impl<R: BufRead> GameSource for PgnGameReader<R> {
+    fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    fn parse(&self, game: &str) -> Result<BoardMetaData, String> {
+        parse_lichess_pgn(game)
+    }
+}
+
+// lichess is the only site exporting NDJSON
+impl<R: BufRead> GameSource for NdjsonGameReader<R> {
+    fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    fn parse(&self, game: &str) -> Result<BoardMetaData, String> {
+        parse_lichess_json(game, DEFAULT_LICHESS_URL)
+    }
+}
+
+// NDJSON exports start with a JSON object, PGN with a tag or movetext
+fn is_ndjson(reader: &mut impl BufRead) -> io::Result<bool> {
+    let start = reader.fill_buf()?;
+    Ok(start
+        .iter()
+        .find(|b| !b.is_ascii_whitespace() && !matches!(b, 0xef | 0xbb | 0xbf))
+        .map(|b| *b == b'{')
+        .unwrap_or(false))
+}
+
+fn run_import(
+    app: &AppHandle,
+    database: &Database,
+    mut games: impl GameSource,
+    total_bytes: u64,
+    cancel: &AtomicBool,
+    report: &mut ImportReport,
+) -> Result<(), Box<dyn Error>> {
+    let mut con = database.connection()?;
+
+    loop {
+        let tx = con.transaction()?;
+        let mut batch_len = 0;
+        let mut batch = ImportCounts::default();
+        while batch_len < IMPORT_BATCH_SIZE {
+            if cancel.load(Ordering::Relaxed) {
+                // dropping `tx` rolls back the unfinished batch
+                report.cancelled = true;
+                return Ok(());
+            }
+            let Some(game) = games.next() else {
+                break;
+            };
+            let game = game?;
+            report.processed += 1;
+            batch_len += 1;
+
+            match games.parse(&game) {
+                Ok(metadata) => {
+                    batch.add(import_game(&tx, &metadata)?);
+                }
+                Err(e) => {
+                    report.failed += 1;
+                    if report.errors.len() < MAX_REPORTED_ERRORS {
+                        report.errors.push(ImportError {
+                            index: report.processed,
+                            error: e,
+                        });
+                    }
+                }
+            }
+        }
+        tx.commit()?;
+        report.imported += batch.new;
+        report.duplicates += batch.duplicates;
+        report.updated += batch.updated;
+
+        let percent = if total_bytes > 0 {
+            (games.bytes_read() as f64 / total_bytes as f64 * 100.0) as f32
+        } else {
+            100.0
+        };
+        app.emit(
+            "import_progress",
+            ImportProgress {
+                processed: report.processed,
+                imported: report.imported,
+                duplicates: report.duplicates,
+                updated: report.updated,
+                failed: report.failed,
+                percent,
+            },
+        )
+        .inspect_err(|e| eprintln!("[Import] failed to emit progress: {e}"))
+        .ok();
+
+        if batch_len < IMPORT_BATCH_SIZE {
+            return Ok(());
+        }
+    }
+}
+
+/// Start importing every game of a PGN file (or a lichess NDJSON export) in the background.
+/// Progress is reported through `import_progress` events and the final
+/// `ImportReport` through `import_finished`.
+#[tauri::command]
+pub fn import_pgn_file(
+    app: AppHandle,
+    state: tauri::State<'_, Mutex<ServerState>>,
+    path: String,
+) -> Result<(), String> {
+    let file = File::open(&path).map_err(|e| format!("Could not open {}: {}", path, e))?;
+    let total_bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let database = {
+        let mut state = state.lock().unwrap();
+        state.import_cancel = cancel.clone();
+        state.database.clone()
+    };
+
+    thread::spawn(move || {
+        let mut report = ImportReport::default();
+        let mut reader = BufReader::new(file);
+        let result = match is_ndjson(&mut reader) {
+            Ok(true) => run_import(
+                &app,
+                &database,
+                NdjsonGameReader::new(reader),
+                total_bytes,
+                &cancel,
+                &mut report,
+            ),
+            Ok(false) => run_import(
+                &app,
+                &database,
+                PgnGameReader::new(reader),
+                total_bytes,
+                &cancel,
+                &mut report,
+            ),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            eprintln!("[Import] {} stopped: {}", path, e);
+            report.error = Some(e.to_string());
+        }
+        println!(
+            "[Import] {}: {} imported, {} duplicates, {} updated, {} failed{}",
+            path,
+            report.imported,
+            report.duplicates,
+            report.updated,
+            report.failed,
+            if report.cancelled { " (cancelled)" } else { "" }
+        );
+        app.emit("import_finished", report)
+            .inspect_err(|e| eprintln!("[Import] failed to emit report: {e}"))
+            .ok();
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn cancel_import(state: tauri::State<'_, Mutex<ServerState>>) {
+    let state = state.lock().unwrap();
+    state.import_cancel.store(true, Ordering::Relaxed);
+}
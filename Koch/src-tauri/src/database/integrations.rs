@@ -1,66 +1,364 @@
-use std::{fs, sync::Mutex};
+use std::{sync::Mutex, time::Duration};
 
-use chrono::{Datelike, Local};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use ts_rs::TS;
 
-use crate::{database::create::load_pgn_game, server::server::ServerState};
+use crate::{
+    database::{
+        db::Database,
+        duplicates::{import_game, ImportCounts},
+    },
+    engine::pgn::parse_pgn,
+    server::server::ServerState,
+};
+
+pub const CHESSDOTCOM_USER_SETTING: &str = "chessdotcom_user";
+// overridable so the sync can be pointed at a local mock
+pub const CHESSDOTCOM_URL_SETTING: &str = "chessdotcom_base_url";
+pub const DEFAULT_CHESSDOTCOM_URL: &str = "https://api.chess.com";
+const CHESSDOTCOM_SOURCE: &str = "chess.com";
+// chess.com asks clients to identify themselves and to retry politely on 429
+const USER_AGENT: &str = "Koch chess app";
+const MAX_ATTEMPTS: u32 = 4;
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Serialize, Deserialize)]
 struct ChessGame {
     url: String,
+    #[serde(default)]
     pgn: String,
+    // "chess", "chess960", "bughouse", ...
+    rules: Option<String>,
 }
 #[derive(Debug, Serialize, Deserialize)]
 struct ChessDotComGames {
     games: Vec<ChessGame>,
 }
-async fn get_chessdotcom_games(
-    chessdotcom_user: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let user_agent = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/119.0.0.0 Safari/537.36";
-    let now = Local::now();
-    let current_year = now.year();
-    let current_month = now.month();
-    let url = format!(
-        "https://api.chess.com/pub/player/{}/games/{current_year}/09",
-        chessdotcom_user
-    );
-    let client = Client::builder()
-        .user_agent(user_agent) // This is the magic line
-        .build()?;
-    println!("{}", &url);
-    //let response = client.get(url).send().await?;
-    //let text = response.text().await?;
-    //println!("Successfully fetched response: {}", &text);
-    let text =
-        fs::read_to_string("/home/petru/storage/Projects/chess_app/Koch/src-tauri/src/games.json")?;
-    Ok(text)
+#[derive(Debug, Serialize, Deserialize)]
+struct ChessDotComArchives {
+    archives: Vec<String>,
+}
+
+/// Games imported from one monthly archive.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct MonthSync {
+    // "YYYY/MM"
+    pub month: String,
+    pub counts: ImportCounts,
+}
+
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct SyncReport {
+    pub months: Vec<MonthSync>,
+    pub total: ImportCounts,
+    // set when the sync stopped early; the months before it are kept
+    pub error: Option<String>,
+}
+
+/// Last archive month fully imported for `username` from `source`.
+pub fn last_synced_month(
+    con: &Connection,
+    source: &str,
+    username: &str,
+) -> rusqlite::Result<Option<String>> {
+    con.query_row(
+        "SELECT last_month FROM sync_state WHERE source = ?1 AND username = ?2",
+        params![source, username.to_lowercase()],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(Option::flatten)
+}
+
+pub fn record_synced_month(
+    con: &Connection,
+    source: &str,
+    username: &str,
+    month: &str,
+) -> rusqlite::Result<()> {
+    con.execute(
+        "INSERT INTO sync_state (source, username, last_month, synced_at)
+         VALUES (?1, ?2, ?3, datetime('now'))
+         ON CONFLICT(source, username) DO UPDATE SET
+            last_month = MAX(COALESCE(sync_state.last_month, ''), excluded.last_month),
+            synced_at = excluded.synced_at",
+        params![source, username.to_lowercase(), month],
+    )?;
+    Ok(())
 }
+
+// "https://api.chess.com/pub/player/x/games/2024/09" -> "2024/09"
+fn archive_month(archive_url: &str) -> Option<String> {
+    let mut parts = archive_url.trim_end_matches('/').rsplit('/');
+    let month = parts.next()?;
+    let year = parts.next()?;
+    if month.len() != 2
+        || year.len() != 4
+        || !format!("{year}{month}").bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    Some(format!("{}/{}", year, month))
+}
+
+/// GET `url`, waiting and retrying on rate limits (429), server errors and
+/// dropped connections. The wait doubles every attempt unless the server
+/// sends `Retry-After`.
+async fn get_with_retries(client: &Client, url: &str) -> Result<String, String> {
+    let mut wait = Duration::from_secs(1);
+    for attempt in 1..=MAX_ATTEMPTS {
+        let last_attempt = attempt == MAX_ATTEMPTS;
+        let retry_after = match client.get(url).send().await {
+            Ok(response) if response.status().is_success() => {
+                return response.text().await.map_err(|e| e.to_string());
+            }
+            Ok(response)
+                if !last_attempt
+                    && (response.status() == StatusCode::TOO_MANY_REQUESTS
+                        || response.status().is_server_error()) =>
+            {
+                println!("[Import] {} returned {}, retrying", url, response.status());
+                response
+                    .headers()
+                    .get("Retry-After")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse::<u64>().ok())
+                    .map(Duration::from_secs)
+            }
+            Ok(response) => return Err(format!("{} returned {}", url, response.status())),
+            Err(e) if !last_attempt && (e.is_connect() || e.is_timeout()) => {
+                println!("[Import] {} failed ({}), retrying", url, e);
+                None
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        tokio::time::sleep(retry_after.unwrap_or(wait).min(MAX_RETRY_WAIT)).await;
+        wait *= 2;
+    }
+    Err(format!("{} kept failing", url))
+}
+
 fn load_chessdotcom_games(
+    con: &mut Connection,
     chessdotcom_api_response: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<ImportCounts, Box<dyn std::error::Error>> {
     let games_data: ChessDotComGames = serde_json::from_str(&chessdotcom_api_response)?;
+    let mut counts = ImportCounts::default();
+    let tx = con.transaction()?;
     for game in &games_data.games {
-        load_pgn_game(game.pgn.clone())?;
+        if game.rules.as_deref().is_some_and(|rules| rules != "chess") || game.pgn.is_empty() {
+            continue;
+        }
+        match parse_pgn(&game.pgn) {
+            Ok(metadata) => counts.add(import_game(&tx, &metadata)?),
+            Err(e) => {
+                eprintln!("[Import] skipped {}: {}", game.url, e);
+                counts.failed += 1;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(counts)
+}
+
+/// Import the monthly archives of `username` that are not older than the last
+/// synced month. That month is fetched again since it may have been synced
+/// before it was over; games already stored are skipped.
+pub async fn sync_chessdotcom_user(
+    database: &Database,
+    base_url: &str,
+    username: &str,
+) -> SyncReport {
+    let mut report = SyncReport::default();
+    if let Err(e) = sync_months(database, base_url, username, &mut report).await {
+        eprintln!("[Import] chess.com sync of {} stopped: {}", username, e);
+        report.error = Some(e);
+    }
+    report
+}
+
+async fn sync_months(
+    database: &Database,
+    base_url: &str,
+    username: &str,
+    report: &mut SyncReport,
+) -> Result<(), String> {
+    let client = Client::builder()
+        .user_agent(USER_AGENT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let player_url = format!("{}/pub/player/{}/games", base_url, username.to_lowercase());
+    let archives: ChessDotComArchives = serde_json::from_str(
+        &get_with_retries(&client, &format!("{}/archives", player_url)).await?,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let last_month = {
+        let con = database.connection().map_err(|e| e.to_string())?;
+        last_synced_month(&con, CHESSDOTCOM_SOURCE, username).map_err(|e| e.to_string())?
+    };
+    let mut months: Vec<String> = archives
+        .archives
+        .iter()
+        .filter_map(|url| archive_month(url))
+        .filter(|month| last_month.as_ref().is_none_or(|last| month >= last))
+        .collect();
+    months.sort();
+
+    for month in months {
+        // archive URLs point at the real API, rebuild them on the configured base
+        let games = get_with_retries(&client, &format!("{}/{}", player_url, month)).await?;
+        let mut con = database.connection().map_err(|e| e.to_string())?;
+        let counts =
+            load_chessdotcom_games(&mut con, &games).map_err(|e| format!("{}: {}", month, e))?;
+        record_synced_month(&con, CHESSDOTCOM_SOURCE, username, &month)
+            .map_err(|e| e.to_string())?;
+        println!(
+            "[Import] chess.com {} {}: {} new, {} duplicates, {} updated",
+            username, month, counts.new, counts.duplicates, counts.updated
+        );
+        report.total.merge(&counts);
+        report.months.push(MonthSync { month, counts });
     }
     Ok(())
 }
+
 #[tauri::command]
 pub async fn sync_with_chessdotcom(
     state: tauri::State<'_, Mutex<ServerState<'_>>>,
-) -> Result<(), String> {
-    // Lock only to extract the username, then drop the lock before await
-    let chessdotcom_user = {
+) -> Result<SyncReport, String> {
+    // Lock only to read the settings, then drop the lock before await
+    let (chessdotcom_user, base_url, database) = {
         let state = state.lock().map_err(|e| e.to_string())?;
-        match state.settings.map.get("chessdotcom_user") {
-            Some(cu) => cu.clone(),
-            None => return Err("nu chieie in setari".to_string()),
-        }
+        let setting = |key: &str| {
+            state
+                .settings
+                .map
+                .get(key)
+                .map(|value| value.trim().trim_end_matches('/').to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let user = setting(CHESSDOTCOM_USER_SETTING).ok_or("No chess.com user in the settings")?;
+        let base_url =
+            setting(CHESSDOTCOM_URL_SETTING).unwrap_or(DEFAULT_CHESSDOTCOM_URL.to_string());
+        (user, base_url, state.database.clone())
     };
-    let api_response = get_chessdotcom_games(&chessdotcom_user)
-        .await
-        .map_err(|e| e.to_string())?;
-    load_chessdotcom_games(&api_response).map_err(|e| e.to_string())?;
-    Ok(())
+    Ok(sync_chessdotcom_user(&database, &base_url, &chessdotcom_user).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write as _};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn archive(games: &[(&str, &str)]) -> String {
+        let games: Vec<_> = games
+            .iter()
+            .map(|(rules, moves)| {
+                serde_json::json!({
+                    "url": format!("https://www.chess.com/game/live/{}", moves.len()),
+                    "pgn": format!("[White \"a\"]\n[Black \"b\"]\n\n{} *", moves),
+                    "rules": rules,
+                })
+            })
+            .collect();
+        serde_json::json!({ "games": games }).to_string()
+    }
+
+    // Answers one request per (status, headers, body), sends back the request lines.
+    fn stub_server(
+        responses: Vec<(&'static str, &'static str, String)>,
+    ) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for (status, headers, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8_lossy(&request);
+                tx.send(request.lines().next().unwrap_or_default().to_string())
+                    .unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    headers,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        (base_url, rx)
+    }
+
+    #[test]
+    fn sync_resumes_from_the_last_month_and_retries_rate_limits() {
+        let archives = serde_json::json!({
+            "archives": [
+                "https://api.chess.com/pub/player/b/games/2024/01",
+                "https://api.chess.com/pub/player/b/games/2024/02",
+                "https://api.chess.com/pub/player/b/games/2024/03",
+            ]
+        })
+        .to_string();
+        let (base_url, requests) = stub_server(vec![
+            ("200 OK", "", archives),
+            ("429 Too Many Requests", "Retry-After: 0\r\n", String::new()),
+            ("200 OK", "", archive(&[("chess", "1. e4 e5")])),
+            (
+                "200 OK",
+                "",
+                archive(&[("chess", "1. d4 d5"), ("chess960", "1. c4 c5")]),
+            ),
+        ]);
+        let database = Database::in_memory().unwrap();
+        record_synced_month(
+            &database.connection().unwrap(),
+            CHESSDOTCOM_SOURCE,
+            "B",
+            "2024/02",
+        )
+        .unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let report = runtime.block_on(sync_chessdotcom_user(&database, &base_url, "B"));
+        assert_eq!(report.error, None);
+        let months: Vec<_> = report
+            .months
+            .iter()
+            .map(|month| (month.month.as_str(), month.counts.new))
+            .collect();
+        assert_eq!(months, [("2024/02", 1), ("2024/03", 1)]);
+        assert_eq!(report.total.new, 2);
+
+        let requests: Vec<String> = requests.try_iter().collect();
+        assert_eq!(
+            requests,
+            [
+                "GET /pub/player/b/games/archives HTTP/1.1",
+                "GET /pub/player/b/games/2024/02 HTTP/1.1",
+                "GET /pub/player/b/games/2024/02 HTTP/1.1",
+                "GET /pub/player/b/games/2024/03 HTTP/1.1",
+            ]
+        );
+        let con = database.connection().unwrap();
+        assert_eq!(
+            last_synced_month(&con, CHESSDOTCOM_SOURCE, "b").unwrap(),
+            Some("2024/03".to_string())
+        );
+    }
 }
@@ -0,0 +1,520 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::io::{self, BufRead};
+use std::sync::Mutex;
+
+use chrono::DateTime;
+use reqwest::Client;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+
+use crate::database::db::Database;
+use crate::database::duplicates::{import_game, ImportCounts};
+use crate::engine::board::{BoardMetaData, EvalResponse, EvalType, GameResult};
+use crate::engine::pgn::{find_tag, format_eval_command, parse_pgn, UNKNOWN_DATE};
+use crate::etc::DEFAULT_FEN;
+use crate::game::controller::TerminationReason;
+use crate::server::server::ServerState;
+
+pub const LICHESS_USER_SETTING: &str = "lichess_user";
+// overridable so the sync can be pointed at a local stub server
+pub const LICHESS_URL_SETTING: &str = "lichess_base_url";
+pub const DEFAULT_LICHESS_URL: &str = "https://lichess.org";
+const LICHESS_SOURCE: &str = "lichess";
+
+#[derive(Debug, Deserialize)]
+struct LichessUser {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LichessPlayer {
+    user: Option<LichessUser>,
+    rating: Option<u32>,
+    ai_level: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LichessPlayers {
+    white: LichessPlayer,
+    black: LichessPlayer,
+}
+
+#[derive(Debug, Deserialize)]
+struct LichessOpening {
+    eco: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LichessClock {
+    initial: u32,
+    increment: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct LichessEval {
+    eval: Option<i32>,
+    mate: Option<i32>,
+}
+
+/// One game of the lichess NDJSON export (`Accept: application/x-ndjson`).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LichessGame {
+    id: String,
+    #[serde(default)]
+    rated: bool,
+    variant: Option<String>,
+    speed: Option<String>,
+    created_at: Option<i64>,
+    status: Option<String>,
+    players: LichessPlayers,
+    winner: Option<String>,
+    opening: Option<LichessOpening>,
+    #[serde(default)]
+    moves: String,
+    clock: Option<LichessClock>,
+    // remaining time after every move, in centiseconds (`clocks=true`)
+    clocks: Option<Vec<u32>>,
+    // one entry per move (`evals=true`, only for analysed games)
+    analysis: Option<Vec<LichessEval>>,
+    initial_fen: Option<String>,
+    // full PGN when exported with `pgnInJson=true`
+    pgn: Option<String>,
+}
+
+// Variants the board can play; anything else would fail on its first odd move.
+fn variant_name(variant: &str) -> Option<&'static str> {
+    match variant {
+        "standard" | "Standard" => Some("Standard"),
+        "fromPosition" | "From Position" => Some("From Position"),
+        _ => None,
+    }
+}
+
+fn player_name(player: &LichessPlayer) -> String {
+    match (&player.user, player.ai_level) {
+        (Some(user), _) => user.name.clone(),
+        (None, Some(level)) => format!("Stockfish level {}", level),
+        (None, None) => "Anonymous".to_string(),
+    }
+}
+
+// lichess writes clocks as h:mm:ss
+fn format_clock(centiseconds: u32) -> String {
+    let seconds = centiseconds / 100;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Termination as lichess reports it. "Normal" (or the `mate`, `resign`, `draw`
+/// statuses of the API) only says the game ended on the board, the moves and
+/// result tell how.
+fn lichess_termination(value: &str, metadata: &BoardMetaData) -> TerminationReason {
+    let mated = metadata
+        .move_list
+        .last()
+        .map(|mv| mv.san.ends_with('#'))
+        .unwrap_or(false);
+    match value {
+        "mate" => TerminationReason::Checkmate,
+        "stalemate" => TerminationReason::StaleMate,
+        "Time forfeit" | "outoftime" | "timeout" => TerminationReason::Timeout,
+        "draw" => TerminationReason::Draw,
+        _ if mated => TerminationReason::Checkmate,
+        _ if matches!(metadata.result, GameResult::Draw) => TerminationReason::Draw,
+        _ => TerminationReason::Resignation,
+    }
+}
+
+pub fn is_lichess_game(metadata: &BoardMetaData) -> bool {
+    metadata
+        .site
+        .as_deref()
+        .map(|site| site.contains("lichess.org"))
+        .unwrap_or(false)
+}
+
+/// Parse a PGN game and, when it comes from lichess, apply lichess conventions:
+/// unsupported variants are rejected, `Termination` is resolved and the game
+/// URL doubles as `Link`.
+pub fn parse_lichess_pgn(pgn: &str) -> Result<BoardMetaData, String> {
+    let mut metadata = parse_pgn(pgn).map_err(|e| e.to_string())?;
+    if !is_lichess_game(&metadata) {
+        return Ok(metadata);
+    }
+    if let Some((_, variant)) = metadata
+        .extra_tags
+        .iter()
+        .find(|(name, _)| name == "Variant")
+    {
+        if variant_name(variant).is_none() {
+            return Err(format!("Unsupported variant: {}", variant));
+        }
+    }
+    if let Some(termination) = find_tag(pgn, "Termination") {
+        metadata.termination = lichess_termination(termination, &metadata);
+    }
+    if metadata.link.is_none() {
+        metadata.link = metadata.site.clone();
+    }
+    Ok(metadata)
+}
+
+impl LichessGame {
+    fn result(&self) -> &'static str {
+        match (self.winner.as_deref(), self.status.as_deref()) {
+            (Some("white"), _) => "1-0",
+            (Some("black"), _) => "0-1",
+            (_, Some("created" | "started" | "aborted" | "noStart")) => "*",
+            _ => "1/2-1/2",
+        }
+    }
+
+    fn to_pgn(&self, base_url: &str) -> Result<String, String> {
+        let variant = self.variant.as_deref().unwrap_or("standard");
+        let variant = variant_name(variant).ok_or(format!("Unsupported variant: {}", variant))?;
+
+        let mut pgn = String::new();
+        let mut tag = |name: &str, value: &str| {
+            writeln!(pgn, "[{} \"{}\"]", name, value.replace('"', "'")).unwrap();
+        };
+        let speed = self.speed.as_deref().unwrap_or("unknown");
+        let rated = if self.rated { "Rated" } else { "Casual" };
+        tag("Event", &format!("{} {} game", rated, speed));
+        tag("Site", &format!("{}/{}", base_url, self.id));
+        let created = self.created_at.and_then(DateTime::from_timestamp_millis);
+        match created {
+            Some(created) => {
+                tag("Date", &created.format("%Y.%m.%d").to_string());
+                tag("UTCTime", &created.format("%H:%M:%S").to_string());
+            }
+            None => tag("Date", UNKNOWN_DATE),
+        }
+        tag("White", &player_name(&self.players.white));
+        tag("Black", &player_name(&self.players.black));
+        tag("Result", self.result());
+        tag(
+            "WhiteElo",
+            &self.players.white.rating.unwrap_or(0).to_string(),
+        );
+        tag(
+            "BlackElo",
+            &self.players.black.rating.unwrap_or(0).to_string(),
+        );
+        tag("Variant", variant);
+        match &self.clock {
+            Some(clock) => tag(
+                "TimeControl",
+                &format!("{}+{}", clock.initial, clock.increment),
+            ),
+            None => tag("TimeControl", "-"),
+        }
+        if let Some(opening) = &self.opening {
+            if let Some(eco) = &opening.eco {
+                tag("ECO", eco);
+            }
+            if let Some(name) = &opening.name {
+                tag("Opening", name);
+            }
+        }
+        let start = self.initial_fen.as_deref().unwrap_or(DEFAULT_FEN);
+        if start != DEFAULT_FEN {
+            tag("SetUp", "1");
+            tag("FEN", start);
+        }
+
+        // move numbers continue from the starting position
+        let mut fields = start.split_whitespace().skip(1);
+        let black_first = fields.next() == Some("b");
+        let first_move = fields
+            .nth(3)
+            .and_then(|n| n.parse::<u32>().ok())
+            .unwrap_or(1);
+        let clocks = self.clocks.as_deref().unwrap_or_default();
+        let analysis = self.analysis.as_deref().unwrap_or_default();
+        pgn.push('\n');
+        for (ply, san) in self.moves.split_whitespace().enumerate() {
+            let ply_from_white = ply + black_first as usize;
+            let number = first_move + (ply_from_white / 2) as u32;
+            if ply_from_white % 2 == 0 {
+                write!(pgn, "{}. ", number).unwrap();
+            } else if ply == 0 {
+                write!(pgn, "{}... ", number).unwrap();
+            }
+            pgn.push_str(san);
+            let mut comment = Vec::new();
+            if let Some(centiseconds) = clocks.get(ply) {
+                comment.push(format!("[%clk {}]", format_clock(*centiseconds)));
+            }
+            let eval = analysis
+                .get(ply)
+                .and_then(|eval| match (eval.mate, eval.eval) {
+                    (Some(mate), _) => Some(EvalResponse {
+                        value: mate as f32,
+                        kind: EvalType::Mate,
+                    }),
+                    (None, Some(cp)) => Some(EvalResponse {
+                        value: cp as f32,
+                        kind: EvalType::Centipawn,
+                    }),
+                    (None, None) => None,
+                });
+            if let Some(eval) = eval {
+                comment.push(format_eval_command(&eval));
+            }
+            if !comment.is_empty() {
+                write!(pgn, " {{ {} }}", comment.join(" ")).unwrap();
+            }
+            pgn.push(' ');
+        }
+        pgn.push_str(self.result());
+        pgn.push('\n');
+        Ok(pgn)
+    }
+}
+
+/// Parse one line of a lichess NDJSON export.
+pub fn parse_lichess_json(line: &str, base_url: &str) -> Result<BoardMetaData, String> {
+    let game: LichessGame = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    if let Some(pgn) = &game.pgn {
+        return parse_lichess_pgn(pgn);
+    }
+    let mut metadata = parse_pgn(&game.to_pgn(base_url)?).map_err(|e| e.to_string())?;
+    if let Some(status) = &game.status {
+        metadata.termination = lichess_termination(status, &metadata);
+    }
+    metadata.link = metadata.site.clone();
+    Ok(metadata)
+}
+
+/// Reads an NDJSON export one game (line) at a time.
+pub struct NdjsonGameReader<R: BufRead> {
+    reader: R,
+    pub bytes_read: u64,
+}
+
+impl<R: BufRead> NdjsonGameReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            bytes_read: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for NdjsonGameReader<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(n) => self.bytes_read += n as u64,
+                Err(e) => return Some(Err(e)),
+            }
+            if !line.trim().is_empty() {
+                return Some(Ok(line));
+            }
+        }
+    }
+}
+
+/// Start time (ms) of the newest lichess game seen for `user` by earlier syncs.
+pub fn last_synced_game(con: &Connection, user: &str) -> rusqlite::Result<Option<i64>> {
+    con.query_row(
+        "SELECT last_game_at FROM sync_state WHERE source = ?1 AND username = ?2",
+        params![LICHESS_SOURCE, user.to_lowercase()],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(Option::flatten)
+}
+
+pub fn record_synced_game(con: &Connection, user: &str, created_at: i64) -> rusqlite::Result<()> {
+    con.execute(
+        "INSERT INTO sync_state (source, username, last_game_at, synced_at)
+         VALUES (?1, ?2, ?3, datetime('now'))
+         ON CONFLICT(source, username) DO UPDATE SET
+            last_game_at = MAX(COALESCE(sync_state.last_game_at, 0), excluded.last_game_at),
+            synced_at = excluded.synced_at",
+        params![LICHESS_SOURCE, user.to_lowercase(), created_at],
+    )?;
+    Ok(())
+}
+
+// Start time of an export line, also for games that fail to parse.
+fn created_at(line: &str) -> Option<i64> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Created {
+        created_at: Option<i64>,
+    }
+    serde_json::from_str::<Created>(line).ok()?.created_at
+}
+
+/// Games of `user` started at `since` (ms) or later, every game when None.
+async fn fetch_lichess_games(
+    base_url: &str,
+    user: &str,
+    since: Option<i64>,
+) -> Result<String, Box<dyn Error>> {
+    let url = format!("{}/api/games/user/{}", base_url, user);
+    println!("[Import] fetching {}", url);
+    let mut query = vec![
+        ("clocks", "true".to_string()),
+        ("evals", "true".to_string()),
+        ("opening", "true".to_string()),
+    ];
+    if let Some(since) = since {
+        query.push(("since", since.to_string()));
+    }
+    let response = Client::new()
+        .get(&url)
+        .query(&query)
+        .header("Accept", "application/x-ndjson")
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.text().await?)
+}
+
+/// Import the games `user` played since the newest one seen by the last sync.
+pub async fn sync_lichess_user(
+    database: &Database,
+    base_url: &str,
+    user: &str,
+) -> Result<ImportCounts, String> {
+    let since = {
+        let con = database.connection().map_err(|e| e.to_string())?;
+        last_synced_game(&con, user).map_err(|e| e.to_string())?
+    };
+    let export = fetch_lichess_games(base_url, user, since.map(|at| at + 1))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut con = database.connection().map_err(|e| e.to_string())?;
+    let tx = con.transaction().map_err(|e| e.to_string())?;
+    let mut counts = ImportCounts::default();
+    let mut newest = None;
+    for line in export.lines().filter(|line| !line.trim().is_empty()) {
+        newest = newest.max(created_at(line));
+        match parse_lichess_json(line, base_url) {
+            Ok(metadata) => counts.add(import_game(&tx, &metadata).map_err(|e| e.to_string())?),
+            Err(e) => {
+                eprintln!("[Import] skipped lichess game: {}", e);
+                counts.failed += 1;
+            }
+        }
+    }
+    if let Some(newest) = newest {
+        record_synced_game(&tx, user, newest).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    println!(
+        "[Import] lichess {}: {} new, {} duplicates, {} updated, {} failed",
+        user, counts.new, counts.duplicates, counts.updated, counts.failed
+    );
+    Ok(counts)
+}
+
+/// Download the games of the configured lichess user played since the last
+/// sync and store the new ones.
+#[tauri::command]
+pub async fn sync_with_lichess(
+    state: tauri::State<'_, Mutex<ServerState<'_>>>,
+) -> Result<ImportCounts, String> {
+    let (user, base_url, database) = {
+        let state = state.lock().map_err(|e| e.to_string())?;
+        let setting = |key: &str| {
+            state
+                .settings
+                .map
+                .get(key)
+                .map(|value| value.trim().trim_end_matches('/').to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let user = setting(LICHESS_USER_SETTING).ok_or("No lichess user in the settings")?;
+        let base_url = setting(LICHESS_URL_SETTING).unwrap_or(DEFAULT_LICHESS_URL.to_string());
+        (user, base_url, state.database.clone())
+    };
+    sync_lichess_user(&database, &base_url, &user).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write as _};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn game(id: &str, variant: &str, created_at: i64) -> String {
+        format!(
+            r#"{{"id":"{id}","rated":true,"variant":"{variant}","speed":"blitz","createdAt":{created_at},"status":"mate","winner":"black","players":{{"white":{{"user":{{"name":"a"}},"rating":1500}},"black":{{"user":{{"name":"b"}},"rating":1600}}}},"moves":"f3 e5 g4 Qh4#","clock":{{"initial":180,"increment":0}}}}"#
+        )
+    }
+
+    // Answers one request per body with that body, sends back the request lines.
+    fn stub_server(bodies: Vec<String>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for body in bodies {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8_lossy(&request);
+                tx.send(request.lines().next().unwrap_or_default().to_string())
+                    .unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        (base_url, rx)
+    }
+
+    #[test]
+    fn sync_resumes_after_the_newest_game() {
+        let export = [
+            game("first", "standard", 1000),
+            game("second", "chess960", 2000),
+        ]
+        .join("\n");
+        let (base_url, requests) = stub_server(vec![export, String::new()]);
+        let database = Database::in_memory().unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let counts = runtime
+            .block_on(sync_lichess_user(&database, &base_url, "B"))
+            .unwrap();
+        assert_eq!((counts.new, counts.failed), (1, 1));
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("GET /api/games/user/B?"));
+        assert!(!request.contains("since"));
+
+        let counts = runtime
+            .block_on(sync_lichess_user(&database, &base_url, "b"))
+            .unwrap();
+        assert_eq!((counts.new, counts.failed), (0, 0));
+        assert!(requests.recv().unwrap().contains("since=2001"));
+    }
+}
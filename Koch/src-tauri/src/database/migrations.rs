@@ -0,0 +1,519 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use rusqlite::{params, Connection, Transaction};
+
+use crate::engine::board::BoardMetaData;
+use crate::engine::pgn::parse_pgn;
+
+/// One schema step. `up` runs inside a transaction together with the
+/// `user_version` bump, so a failing step leaves the database untouched.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// All migrations, in order. Never edit a released step, add a new one instead.
+/// Steps that fill in data keep their own copy of the logic they need, so
+/// later changes to the app don't change what an old step writes. Only the
+/// PGN parser is shared.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "games, chats and messages",
+        up: initial_schema,
+    },
+    Migration {
+        version: 2,
+        description: "positions index",
+        up: positions_table,
+    },
+    Migration {
+        version: 3,
+        description: "game list search columns",
+        up: search_columns,
+    },
+    Migration {
+        version: 4,
+        description: "collections, tags and favorites",
+        up: collections_tables,
+    },
+    Migration {
+        version: 5,
+        description: "engine evaluation cache",
+        up: eval_cache_table,
+    },
+    Migration {
+        version: 6,
+        description: "game fingerprints",
+        up: fingerprint_column,
+    },
+    Migration {
+        version: 7,
+        description: "per-user sync state",
+        up: sync_state_table,
+    },
+    Migration {
+        version: 8,
+        description: "rating history",
+        up: rating_history_table,
+    },
+    Migration {
+        version: 9,
+        description: "whole-game engine analysis",
+        up: game_analysis_table,
+    },
+    Migration {
+        version: 10,
+        description: "accuracy columns",
+        up: accuracy_columns,
+    },
+    Migration {
+        version: 11,
+        description: "engine registry",
+        up: engines_table,
+    },
+    Migration {
+        version: 12,
+        description: "lichess sync timestamp",
+        up: last_game_column,
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn user_version(con: &Connection) -> rusqlite::Result<u32> {
+    con.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Bring the database up to the latest schema. If migrations are pending and
+/// `db_path` points at an existing file, a copy is written next to it first
+/// (`<db>.v<old version>.bak`). Returns the resulting schema version.
+pub fn run_migrations(con: &mut Connection, db_path: Option<&Path>) -> rusqlite::Result<u32> {
+    let current = user_version(con)?;
+    let latest = latest_version();
+    if current > latest {
+        eprintln!(
+            "[DB] database schema v{} is newer than this build (v{}), leaving it as is",
+            current, latest
+        );
+        return Ok(current);
+    }
+    if current == latest {
+        return Ok(current);
+    }
+
+    // a freshly created database file is empty, nothing to back up
+    let has_data = |p: &&Path| p.metadata().map(|m| m.len() > 0).unwrap_or(false);
+    if let Some(path) = db_path.filter(has_data) {
+        let backup = format!("{}.v{}.bak", path.display(), current);
+        if Path::new(&backup).exists() {
+            std::fs::remove_file(&backup)
+                .inspect_err(|e| eprintln!("[DB] could not replace old backup: {e}"))
+                .ok();
+        }
+        con.execute("VACUUM INTO ?1", [&backup])?;
+        println!("[DB] backed up schema v{} to {}", current, backup);
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = con.transaction()?;
+        (migration.up)(&tx)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        println!(
+            "[DB] applied migration {}: {}",
+            migration.version, migration.description
+        );
+    }
+    Ok(latest)
+}
+
+// Databases created before versioning already have these tables, hence IF NOT EXISTS.
+fn initial_schema(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS games (
+            game_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            date_played TEXT,
+            white_player TEXT NOT NULL,
+            black_player TEXT NOT NULL,
+            white_elo NUMBER NOT NULL,
+            black_elo NUMBER NOT NULL,
+            result TEXT NOT NULL,
+            opening TEXT NOT NULL,
+            time_control TEXT,
+            pgn_data TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS chats (
+            chat_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            game_id INTEGER,
+            FOREIGN KEY(game_id) REFERENCES games(game_id)
+            UNIQUE (game_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS messages (
+            message_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            chat_id INTEGER,
+            role TEXT,
+            content TEXT,
+            sent_at TEXT,
+            move_index NUMBER,
+            FOREIGN KEY(chat_id) REFERENCES chats(chat_id),
+            UNIQUE(content)
+        );",
+    )
+}
+
+fn positions_table(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE positions (
+            game_id INTEGER NOT NULL,
+            ply INTEGER NOT NULL,
+            position_hash INTEGER NOT NULL,
+            FOREIGN KEY(game_id) REFERENCES games(game_id)
+        );
+        CREATE INDEX idx_positions_hash ON positions(position_hash);
+        CREATE INDEX idx_positions_game ON positions(game_id);",
+    )?;
+    let mut insert =
+        tx.prepare("INSERT INTO positions (game_id, ply, position_hash) VALUES (?1, ?2, ?3)")?;
+    for (game_id, metadata) in stored_games(tx, "not indexed")? {
+        let tree = &metadata.move_tree;
+        let mut fens = vec![metadata.starting_position.clone()];
+        fens.extend(
+            tree.mainline()
+                .iter()
+                .filter_map(|id| tree.get(*id).map(|node| node.fen.clone())),
+        );
+        for (ply, fen) in fens.iter().enumerate() {
+            insert.execute(params![game_id, ply as i64, v2_position_hash(fen)])?;
+        }
+    }
+    Ok(())
+}
+
+fn search_columns(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE games ADD COLUMN eco TEXT;
+        ALTER TABLE games ADD COLUMN site TEXT;
+        ALTER TABLE games ADD COLUMN time_class TEXT;
+        ALTER TABLE games ADD COLUMN played_on TEXT;
+        CREATE INDEX idx_games_white ON games(white_player COLLATE NOCASE);
+        CREATE INDEX idx_games_black ON games(black_player COLLATE NOCASE);
+        CREATE INDEX idx_games_played_on ON games(played_on);
+        CREATE INDEX idx_games_result ON games(result);
+        CREATE INDEX idx_games_eco ON games(eco);
+        CREATE INDEX idx_games_time_class ON games(time_class);
+        CREATE INDEX idx_games_elo ON games(white_elo, black_elo);",
+    )?;
+    for (game_id, metadata) in stored_games(tx, "not backfilled")? {
+        let time_class = metadata.time_control.as_deref().and_then(v3_time_class);
+        tx.execute(
+            "UPDATE games SET eco = ?1, site = ?2, time_class = ?3, played_on = ?4
+             WHERE game_id = ?5",
+            params![
+                metadata.eco,
+                metadata.site,
+                time_class,
+                v3_played_on(&metadata.date),
+                game_id
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn collections_tables(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE collections (
+            collection_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            description TEXT,
+            created_at TEXT
+        );
+        CREATE TABLE collection_games (
+            collection_id INTEGER NOT NULL,
+            game_id INTEGER NOT NULL,
+            added_at TEXT,
+            PRIMARY KEY (collection_id, game_id),
+            FOREIGN KEY(collection_id) REFERENCES collections(collection_id),
+            FOREIGN KEY(game_id) REFERENCES games(game_id)
+        );
+        CREATE INDEX idx_collection_games_game ON collection_games(game_id);
+
+        CREATE TABLE game_tags (
+            game_id INTEGER NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (game_id, tag),
+            FOREIGN KEY(game_id) REFERENCES games(game_id)
+        );
+        CREATE INDEX idx_game_tags_tag ON game_tags(tag);
+
+        CREATE TABLE favorites (
+            game_id INTEGER PRIMARY KEY,
+            added_at TEXT,
+            FOREIGN KEY(game_id) REFERENCES games(game_id)
+        );",
+    )
+}
+
+fn eval_cache_table(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE eval_cache (
+            position_hash INTEGER NOT NULL,
+            engine TEXT NOT NULL,
+            multipv INTEGER NOT NULL,
+            depth INTEGER NOT NULL,
+            lines TEXT NOT NULL,
+            updated_at TEXT,
+            PRIMARY KEY (position_hash, engine, multipv)
+        );",
+    )
+}
+
+fn fingerprint_column(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch("ALTER TABLE games ADD COLUMN fingerprint TEXT;")?;
+    // when old data holds the same game twice, only the first copy gets a
+    // fingerprint so the unique index can be built
+    let mut seen = HashSet::new();
+    for (game_id, metadata) in stored_games(tx, "not fingerprinted")? {
+        let fingerprint = v6_fingerprint(&metadata);
+        if !seen.insert(fingerprint.clone()) {
+            println!("[DB] game {} is a duplicate of an earlier game", game_id);
+            continue;
+        }
+        tx.execute(
+            "UPDATE games SET fingerprint = ?1 WHERE game_id = ?2",
+            params![fingerprint, game_id],
+        )?;
+    }
+    tx.execute_batch("CREATE UNIQUE INDEX idx_games_fingerprint ON games(fingerprint);")
+}
+
+fn sync_state_table(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE sync_state (
+            source TEXT NOT NULL,
+            username TEXT NOT NULL,
+            last_month TEXT,
+            synced_at TEXT,
+            PRIMARY KEY (source, username)
+        );",
+    )
+}
+
+fn rating_history_table(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE rating_history (
+            entry_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            time_class TEXT NOT NULL,
+            game_id INTEGER,
+            rating_before INTEGER NOT NULL,
+            rating_after INTEGER NOT NULL,
+            opponent_rating INTEGER NOT NULL,
+            score REAL NOT NULL,
+            recorded_at TEXT,
+            FOREIGN KEY(game_id) REFERENCES games(game_id)
+        );
+        CREATE INDEX idx_rating_history_class ON rating_history(time_class);",
+    )
+}
+
+fn game_analysis_table(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE game_analysis (
+            game_id INTEGER PRIMARY KEY,
+            engine TEXT NOT NULL,
+            depth INTEGER,
+            movetime INTEGER,
+            plies TEXT NOT NULL,
+            analyzed_at TEXT,
+            FOREIGN KEY(game_id) REFERENCES games(game_id)
+        );",
+    )
+}
+
+fn accuracy_columns(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE games ADD COLUMN white_accuracy REAL;
+        ALTER TABLE games ADD COLUMN black_accuracy REAL;
+        ALTER TABLE games ADD COLUMN white_acpl REAL;
+        ALTER TABLE games ADD COLUMN black_acpl REAL;",
+    )
+}
+
+fn engines_table(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE engines (
+            engine_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            path TEXT NOT NULL,
+            args TEXT NOT NULL,
+            options TEXT NOT NULL,
+            option_values TEXT NOT NULL,
+            added_at TEXT
+        );",
+    )
+}
+
+fn last_game_column(tx: &Transaction) -> rusqlite::Result<()> {
+    // lichess syncs resume from the start time of the newest game seen, in ms
+    tx.execute_batch("ALTER TABLE sync_state ADD COLUMN last_game_at INTEGER;")
+}
+
+// Every stored game that parses, oldest first. `skipped` ends the log line
+// of a game that doesn't.
+fn stored_games(tx: &Transaction, skipped: &str) -> rusqlite::Result<Vec<(i64, BoardMetaData)>> {
+    let games: Vec<(i64, String)> = {
+        let mut stmt = tx.prepare("SELECT game_id, pgn_data FROM games ORDER BY game_id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+    Ok(games
+        .into_iter()
+        .filter_map(|(game_id, pgn_data)| match parse_pgn(&pgn_data) {
+            Ok(metadata) => Some((game_id, metadata)),
+            Err(e) => {
+                eprintln!("[DB] game {} {}: {}", game_id, skipped, e);
+                None
+            }
+        })
+        .collect())
+}
+
+// Board, side to move and castling rights of a FEN.
+fn v2_position_key(fen: &str) -> String {
+    let mut parts: Vec<&str> = fen.split_whitespace().take(3).collect();
+    parts.push("-");
+    parts.join(" ")
+}
+
+// 64-bit FNV-1a of the position key.
+fn v2_position_hash(fen: &str) -> i64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in v2_position_key(fen).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash as i64
+}
+
+// Time class of a PGN TimeControl, from the time a 40 move game takes.
+fn v3_time_class(time_control: &str) -> Option<&'static str> {
+    let first = time_control.split(':').next()?.trim();
+    if first.contains('/') {
+        return Some("classical");
+    }
+    let (base, increment) = match first.split_once('+') {
+        Some((base, inc)) => (base.parse::<f32>().ok()?, inc.parse::<f32>().ok()?),
+        None => (first.parse::<f32>().ok()?, 0.0),
+    };
+    let estimated = base + 40.0 * increment;
+    Some(if estimated < 180.0 {
+        "bullet"
+    } else if estimated < 480.0 {
+        "blitz"
+    } else if estimated < 1500.0 {
+        "rapid"
+    } else {
+        "classical"
+    })
+}
+
+// PGN date as YYYY-MM-DD, unknown month and day as 01.
+fn v3_played_on(date: &str) -> Option<String> {
+    let mut parts = date.split(['.', '-', '/', ' ', 'T']);
+    let year = parts.next()?.trim();
+    if year.len() != 4 || !year.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let month = parts
+        .next()
+        .and_then(|m| m.parse::<u32>().ok())
+        .unwrap_or(1);
+    let day = parts
+        .next()
+        .and_then(|d| d.parse::<u32>().ok())
+        .unwrap_or(1);
+    Some(format!(
+        "{}-{:02}-{:02}",
+        year,
+        month.clamp(1, 12),
+        day.clamp(1, 31)
+    ))
+}
+
+// Players, day played, start position and main line, hashed twice with FNV-1a.
+fn v6_fingerprint(metadata: &BoardMetaData) -> String {
+    let player = |name: &str| {
+        name.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    };
+    let mut canonical = format!(
+        "{}|{}|{}|{}|",
+        player(&metadata.white_player_name),
+        player(&metadata.black_player_name),
+        v3_played_on(&metadata.date).unwrap_or_default(),
+        v2_position_key(&metadata.starting_position),
+    );
+    for mv in &metadata.move_list {
+        canonical.push_str(&mv.uci);
+        canonical.push(' ');
+    }
+    let fnv = |offset: u64| {
+        canonical.bytes().fold(offset, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    };
+    format!(
+        "{:016x}{:016x}",
+        fnv(0xcbf29ce484222325),
+        fnv(0x84222325cbf29ce4)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_games_are_backfilled() {
+        let mut con = Connection::open_in_memory().unwrap();
+        let tx = con.transaction().unwrap();
+        initial_schema(&tx).unwrap();
+        tx.pragma_update(None, "user_version", 1).unwrap();
+        tx.execute(
+            "INSERT INTO games (date_played, white_player, black_player, white_elo, black_elo,
+                result, opening, pgn_data)
+             VALUES ('', 'a', 'b', 1500, 1500, '1-0', '', ?1)",
+            [
+                "[White \"a\"]\n[Black \"b\"]\n[Date \"2024.03.??\"]\n[TimeControl \"180+2\"]\n\n\
+             1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0",
+            ],
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        assert_eq!(run_migrations(&mut con, None).unwrap(), latest_version());
+        let positions: i64 = con
+            .query_row("SELECT COUNT(*) FROM positions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(positions, 8);
+        let (time_class, played_on, fingerprint): (String, String, Option<String>) = con
+            .query_row(
+                "SELECT time_class, played_on, fingerprint FROM games",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(time_class, "blitz");
+        assert_eq!(played_on, "2024-03-01");
+        assert_eq!(fingerprint.map(|f| f.len()), Some(32));
+    }
+}
@@ -1,2 +1,15 @@
+pub mod collections;
 pub mod create;
+pub mod db;
+pub mod duplicates;
+pub mod edit;
+pub mod engines;
+pub mod eval_cache;
+pub mod export;
+pub mod import;
 pub mod integrations;
+pub mod lichess;
+pub mod migrations;
+pub mod positions;
+pub mod query;
+pub mod stats;
@@ -0,0 +1,102 @@
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::database::create::{game_list_row, GAME_LIST_COLUMNS};
+use crate::database::db::state_connection;
+use crate::engine::board::BoardMetaData;
+use crate::engine::fen::{position_hash, position_key};
+use crate::engine::Board;
+use crate::server::server::ServerState;
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export)]
+pub struct PositionMatch {
+    pub game_id: u32,
+    // half-moves played when the position first appears, 0 = starting position
+    pub ply: u32,
+    pub meta_data: BoardMetaData,
+}
+
+/// FEN after every main line move, starting position first.
+/// Games with only a flat move list are replayed to get them.
+pub fn mainline_fens(metadata: &BoardMetaData) -> Vec<String> {
+    let mut fens = vec![metadata.starting_position.clone()];
+    let tree = &metadata.move_tree;
+    if !tree.is_empty() {
+        fens.extend(
+            tree.mainline()
+                .iter()
+                .filter_map(|id| tree.get(*id))
+                .map(|node| node.fen.clone()),
+        );
+        return fens;
+    }
+
+    let mut board = Board::from(&metadata.starting_position);
+    board.rerender_move_cache();
+    for mv in &metadata.move_list {
+        let Some((from, to, promotion)) = board.decode_uci_move(&mv.uci) else {
+            break;
+        };
+        if board.move_piece(from, to, promotion).is_err() {
+            break;
+        }
+        board.rerender_move_cache();
+        fens.push(board.to_string());
+    }
+    fens
+}
+
+/// Replace the indexed positions of a game with those of its current main line.
+pub fn index_game_positions(
+    con: &Connection,
+    game_id: i64,
+    metadata: &BoardMetaData,
+) -> rusqlite::Result<()> {
+    con.execute("DELETE FROM positions WHERE game_id = ?1", [game_id])?;
+    let mut stmt = con.prepare_cached(
+        "INSERT INTO positions (game_id, ply, position_hash) VALUES (?1, ?2, ?3)",
+    )?;
+    for (ply, fen) in mainline_fens(metadata).iter().enumerate() {
+        stmt.execute(params![game_id, ply as i64, position_hash(fen)])?;
+    }
+    Ok(())
+}
+
+pub fn find_games_by_fen(con: &Connection, fen: &str) -> rusqlite::Result<Vec<PositionMatch>> {
+    let columns = GAME_LIST_COLUMNS
+        .iter()
+        .map(|c| format!("g.{}", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut stmt = con.prepare(&format!(
+        "SELECT {}, MIN(p.ply) FROM positions p JOIN games g ON g.game_id = p.game_id
+         WHERE p.position_hash = ?1
+         GROUP BY g.game_id
+         ORDER BY g.game_id",
+        columns
+    ))?;
+    let rows = stmt.query_map([position_hash(fen)], |row| {
+        Ok(PositionMatch {
+            game_id: row.get::<_, i64>(0)? as u32,
+            ply: row.get::<_, i64>(GAME_LIST_COLUMNS.len())? as u32,
+            meta_data: game_list_row(row)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Every stored game in which `fen` occurred on the main line. Clocks in the
+/// FEN are ignored.
+#[tauri::command]
+pub fn search_games_by_fen(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    fen: String,
+) -> Result<Vec<PositionMatch>, String> {
+    let con = state_connection(&state)?;
+    println!("[DB] searching position {}", position_key(&fen));
+    find_games_by_fen(&con, &fen).map_err(|e| e.to_string())
+}
@@ -0,0 +1,426 @@
+use std::sync::Mutex;
+
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::database::create::{game_list_row, GAME_LIST_COLUMNS};
+use crate::database::db::state_connection;
+use crate::engine::board::{BoardMetaData, GameResult};
+use crate::engine::PieceColor;
+use crate::game::controller::GameControllerMode;
+use crate::server::server::ServerState;
+
+/// Search filter over stored games. Every field is optional and the set
+/// fields are combined with AND.
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct GameFilter {
+    // case-insensitive start of either player's name, so the name indexes apply
+    pub player: Option<String>,
+    // only games where `player` had this color, needs `player`
+    pub color: Option<PieceColor>,
+    pub result: Option<GameResult>,
+    // substring of the opening name or ECO code
+    pub opening: Option<String>,
+    // ECO prefix, "B9" matches B90-B99
+    pub eco: Option<String>,
+    // inclusive, "YYYY-MM-DD"
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    // rating of `player` when set, otherwise the average of both players
+    pub min_elo: Option<u32>,
+    pub max_elo: Option<u32>,
+    pub time_class: Option<GameControllerMode>,
+    pub time_control: Option<String>,
+    // substring of the Site tag, e.g. "lichess.org" or "Chess.com"
+    pub site: Option<String>,
+    pub collection_id: Option<u32>,
+    // exact user tag, see `game_tags`
+    pub tag: Option<String>,
+    // Some(true) for favorites only, Some(false) to hide them
+    pub favorite: Option<bool>,
+}
+
+// `value` matched literally by a `LIKE ... ESCAPE '\'` pattern; `_` is common
+// in usernames and would match any character otherwise
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+impl GameFilter {
+    /// Why the filter can't be applied, checked before running it.
+    pub fn validate(&self) -> Result<(), String> {
+        let player = self.player.as_deref().is_some_and(|p| !p.is_empty());
+        if self.color.is_some() && !player {
+            return Err("A color filter needs a player".to_string());
+        }
+        Ok(())
+    }
+
+    /// SQL condition for the `games` table (without `WHERE`) and its parameters.
+    /// An empty filter gives "1 = 1".
+    pub fn to_sql(&self) -> (String, Vec<Value>) {
+        let mut conditions: Vec<String> = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+        let text = |s: &str| Value::Text(s.to_string());
+
+        let player = self.player.as_ref().filter(|p| !p.is_empty());
+        let player_pattern = player.map(|p| format!("{}%", escape_like(p)));
+        match (&player_pattern, self.color) {
+            (Some(pattern), Some(PieceColor::White)) => {
+                conditions.push("white_player LIKE ? ESCAPE '\\'".to_string());
+                values.push(text(pattern));
+            }
+            (Some(pattern), Some(PieceColor::Black)) => {
+                conditions.push("black_player LIKE ? ESCAPE '\\'".to_string());
+                values.push(text(pattern));
+            }
+            (Some(pattern), None) => {
+                conditions.push(
+                    "(white_player LIKE ? ESCAPE '\\' OR black_player LIKE ? ESCAPE '\\')"
+                        .to_string(),
+                );
+                values.push(text(pattern));
+                values.push(text(pattern));
+            }
+            (None, _) => {}
+        }
+        if let Some(result) = self.result.as_ref() {
+            conditions.push("result = ?".to_string());
+            values.push(Value::Text(result.to_string()));
+        }
+        if let Some(opening) = self.opening.as_ref().filter(|o| !o.is_empty()) {
+            conditions.push("(opening LIKE ? ESCAPE '\\' OR eco LIKE ? ESCAPE '\\')".to_string());
+            values.push(Value::Text(format!("%{}%", escape_like(opening))));
+            values.push(Value::Text(format!("{}%", escape_like(opening))));
+        }
+        if let Some(eco) = self.eco.as_ref().filter(|e| !e.is_empty()) {
+            conditions.push("eco LIKE ? ESCAPE '\\'".to_string());
+            values.push(Value::Text(format!(
+                "{}%",
+                escape_like(&eco.to_uppercase())
+            )));
+        }
+        if let Some(from) = self.date_from.as_deref().and_then(played_on) {
+            conditions.push("played_on >= ?".to_string());
+            values.push(Value::Text(from));
+        }
+        if let Some(to) = self.date_to.as_deref().and_then(played_on) {
+            conditions.push("played_on <= ?".to_string());
+            values.push(Value::Text(to));
+        }
+
+        if self.min_elo.is_some() || self.max_elo.is_some() {
+            let (elo_expr, elo_values) = match (&player_pattern, self.color) {
+                (Some(_), Some(PieceColor::White)) => ("white_elo".to_string(), vec![]),
+                (Some(_), Some(PieceColor::Black)) => ("black_elo".to_string(), vec![]),
+                (Some(pattern), None) => (
+                    "(CASE WHEN white_player LIKE ? ESCAPE '\\' THEN white_elo ELSE black_elo END)"
+                        .to_string(),
+                    vec![text(pattern)],
+                ),
+                (None, _) => ("((white_elo + black_elo) / 2)".to_string(), vec![]),
+            };
+            if let Some(min) = self.min_elo {
+                conditions.push(format!("{} >= ?", elo_expr));
+                values.extend(elo_values.iter().cloned());
+                values.push(Value::Integer(min as i64));
+            }
+            if let Some(max) = self.max_elo {
+                conditions.push(format!("{} <= ?", elo_expr));
+                values.extend(elo_values.iter().cloned());
+                values.push(Value::Integer(max as i64));
+            }
+        }
+
+        if let Some(time_class) = self.time_class {
+            conditions.push("time_class = ?".to_string());
+            values.push(text(time_class.as_str()));
+        }
+        if let Some(time_control) = self.time_control.as_ref().filter(|t| !t.is_empty()) {
+            conditions.push("time_control = ?".to_string());
+            values.push(Value::Text(time_control.clone()));
+        }
+        if let Some(site) = self.site.as_ref().filter(|s| !s.is_empty()) {
+            conditions.push("site LIKE ? ESCAPE '\\'".to_string());
+            values.push(Value::Text(format!("%{}%", escape_like(site))));
+        }
+        if let Some(collection_id) = self.collection_id {
+            conditions.push(
+                "game_id IN (SELECT game_id FROM collection_games WHERE collection_id = ?)"
+                    .to_string(),
+            );
+            values.push(Value::Integer(collection_id as i64));
+        }
+        if let Some(tag) = self.tag.as_ref().filter(|t| !t.is_empty()) {
+            conditions.push("game_id IN (SELECT game_id FROM game_tags WHERE tag = ?)".to_string());
+            values.push(Value::Text(tag.trim().to_string()));
+        }
+        if let Some(favorite) = self.favorite {
+            let not = if favorite { "" } else { "NOT " };
+            conditions.push(format!("game_id {}IN (SELECT game_id FROM favorites)", not));
+        }
+
+        if conditions.is_empty() {
+            ("1 = 1".to_string(), values)
+        } else {
+            (conditions.join(" AND "), values)
+        }
+    }
+}
+
+/// Normalize a PGN or app date ("2024.09.15", "2024-09-15 12:00:00",
+/// "2024.??.??") to "YYYY-MM-DD" so dates compare as text. Unknown month or day
+/// become "01".
+pub fn played_on(date: &str) -> Option<String> {
+    let mut parts = date.split(['.', '-', '/', ' ', 'T']);
+    let year = parts.next()?.trim();
+    if year.len() != 4 || !year.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let month = parts
+        .next()
+        .and_then(|m| m.parse::<u32>().ok())
+        .unwrap_or(1);
+    let day = parts
+        .next()
+        .and_then(|d| d.parse::<u32>().ok())
+        .unwrap_or(1);
+    Some(format!(
+        "{}-{:02}-{:02}",
+        year,
+        month.clamp(1, 12),
+        day.clamp(1, 31)
+    ))
+}
+
+#[derive(Clone, Copy, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub enum GameSortField {
+    Id,
+    Date,
+    White,
+    Black,
+    Result,
+    Opening,
+    Eco,
+    AverageElo,
+    WhiteAccuracy,
+    BlackAccuracy,
+    WhiteAcpl,
+    BlackAcpl,
+}
+
+impl GameSortField {
+    fn column(&self) -> &'static str {
+        match self {
+            GameSortField::Id => "game_id",
+            GameSortField::Date => "played_on",
+            GameSortField::White => "white_player COLLATE NOCASE",
+            GameSortField::Black => "black_player COLLATE NOCASE",
+            GameSortField::Result => "result",
+            GameSortField::Opening => "opening COLLATE NOCASE",
+            GameSortField::Eco => "eco",
+            GameSortField::AverageElo => "(white_elo + black_elo)",
+            GameSortField::WhiteAccuracy => "white_accuracy",
+            GameSortField::BlackAccuracy => "black_accuracy",
+            GameSortField::WhiteAcpl => "white_acpl",
+            GameSortField::BlackAcpl => "black_acpl",
+        }
+    }
+}
+
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct GameListQuery {
+    pub filter: GameFilter,
+    pub sort_by: GameSortField,
+    pub descending: bool,
+    // 0 returns every matching game
+    pub limit: u32,
+    pub offset: u32,
+}
+
+/// Overall numbers of an analyzed game, as shown in the game list.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct AccuracySummary {
+    pub white_accuracy: Option<f32>,
+    pub black_accuracy: Option<f32>,
+    pub white_acpl: Option<f32>,
+    pub black_acpl: Option<f32>,
+}
+
+// read after `GAME_LIST_COLUMNS`
+const ACCURACY_COLUMNS: [&str; 4] = [
+    "white_accuracy",
+    "black_accuracy",
+    "white_acpl",
+    "black_acpl",
+];
+
+#[derive(Clone, TS, Serialize)]
+#[ts(export)]
+pub struct GameListPage {
+    pub ids: Vec<u32>,
+    pub games: Vec<BoardMetaData>,
+    // one per game, None when it hasn't been analyzed
+    pub accuracy: Vec<Option<AccuracySummary>>,
+    // number of games matching the filter, ignoring limit/offset
+    pub total: u32,
+}
+
+/// Ids of the games matching `filter`, oldest first.
+pub fn filtered_game_ids(con: &Connection, filter: &GameFilter) -> rusqlite::Result<Vec<i64>> {
+    let (condition, values) = filter.to_sql();
+    let mut stmt = con.prepare(&format!(
+        "SELECT game_id FROM games WHERE {} ORDER BY game_id",
+        condition
+    ))?;
+    let ids = stmt
+        .query_map(params_from_iter(values.iter()), |row| row.get::<_, i64>(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+    Ok(ids)
+}
+
+fn row_accuracy(row: &rusqlite::Row) -> rusqlite::Result<Option<AccuracySummary>> {
+    let first = GAME_LIST_COLUMNS.len();
+    let accuracy = AccuracySummary {
+        white_accuracy: row.get(first)?,
+        black_accuracy: row.get(first + 1)?,
+        white_acpl: row.get(first + 2)?,
+        black_acpl: row.get(first + 3)?,
+    };
+    let analyzed = accuracy.white_accuracy.is_some() || accuracy.black_accuracy.is_some();
+    Ok(analyzed.then_some(accuracy))
+}
+
+pub fn query_game_list(con: &Connection, query: &GameListQuery) -> rusqlite::Result<GameListPage> {
+    let (condition, values) = query.filter.to_sql();
+
+    let total: i64 = con.query_row(
+        &format!("SELECT COUNT(*) FROM games WHERE {}", condition),
+        params_from_iter(values.iter()),
+        |row| row.get(0),
+    )?;
+
+    let direction = if query.descending { "DESC" } else { "ASC" };
+    let mut sql = format!(
+        "SELECT {}, {} FROM games WHERE {} ORDER BY {} {}, game_id {}",
+        GAME_LIST_COLUMNS.join(", "),
+        ACCURACY_COLUMNS.join(", "),
+        condition,
+        query.sort_by.column(),
+        direction,
+        direction
+    );
+    if query.limit > 0 {
+        sql.push_str(&format!(" LIMIT {} OFFSET {}", query.limit, query.offset));
+    }
+    let mut stmt = con.prepare(&sql)?;
+    let rows = stmt
+        .query_map(params_from_iter(values.iter()), |row| {
+            Ok((game_list_row(row)?, row_accuracy(row)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let (games, accuracy): (Vec<BoardMetaData>, _) = rows.into_iter().unzip();
+
+    Ok(GameListPage {
+        ids: games.iter().filter_map(|game| game.game_id).collect(),
+        games,
+        accuracy,
+        total: total as u32,
+    })
+}
+
+#[tauri::command]
+pub fn query_games(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    query: GameListQuery,
+) -> Result<GameListPage, String> {
+    query.filter.validate()?;
+    let con = state_connection(&state)?;
+    query_game_list(&con, &query).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create::insert_game;
+    use crate::database::db::Database;
+    use crate::engine::pgn::parse_pgn;
+
+    #[test]
+    fn color_needs_a_player() {
+        let filter = GameFilter {
+            color: Some(PieceColor::White),
+            ..Default::default()
+        };
+        assert!(filter.validate().is_err());
+        let filter = GameFilter {
+            player: Some("carlsen".to_string()),
+            ..filter
+        };
+        assert!(filter.validate().is_ok());
+    }
+
+    #[test]
+    fn player_search_uses_the_name_indexes() {
+        let database = Database::in_memory().unwrap();
+        let con = database.connection().unwrap();
+        let filter = GameFilter {
+            player: Some("carlsen".to_string()),
+            ..Default::default()
+        };
+        let (condition, values) = filter.to_sql();
+        let mut stmt = con
+            .prepare(&format!(
+                "EXPLAIN QUERY PLAN SELECT game_id FROM games WHERE {}",
+                condition
+            ))
+            .unwrap();
+        let plan: Vec<String> = stmt
+            .query_map(params_from_iter(values.iter()), |row| row.get(3))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        let plan = plan.join("\n");
+        assert!(plan.contains("idx_games_white"), "{}", plan);
+        assert!(plan.contains("idx_games_black"), "{}", plan);
+    }
+
+    #[test]
+    fn wildcards_in_names_are_literal() {
+        let database = Database::in_memory().unwrap();
+        let con = database.connection().unwrap();
+        for name in ["dr_who", "drxwho", "50%er"] {
+            let pgn = format!("[White \"{}\"]\n[Black \"b\"]\n\n1. e4 *", name);
+            insert_game(&con, &parse_pgn(&pgn).unwrap()).unwrap();
+        }
+        let players = |player: &str| -> Vec<String> {
+            let filter = GameFilter {
+                player: Some(player.to_string()),
+                ..Default::default()
+            };
+            let (condition, values) = filter.to_sql();
+            let mut stmt = con
+                .prepare(&format!(
+                    "SELECT white_player FROM games WHERE {} ORDER BY game_id",
+                    condition
+                ))
+                .unwrap();
+            stmt.query_map(params_from_iter(values.iter()), |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap()
+        };
+        assert_eq!(players("dr_"), ["dr_who"]);
+        assert_eq!(players("50%"), ["50%er"]);
+        assert_eq!(players("dr"), ["dr_who", "drxwho"]);
+    }
+}
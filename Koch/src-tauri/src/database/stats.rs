@@ -0,0 +1,420 @@
+use std::sync::Mutex;
+
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::database::db::state_connection;
+use crate::database::positions::mainline_fens;
+use crate::database::query::GameFilter;
+use crate::engine::pgn::parse_pgn;
+use crate::engine::{Board, PieceColor};
+use crate::game::controller::TerminationReason;
+use crate::server::server::ServerState;
+
+// width of the opponent rating bands
+const RATING_BAND: u32 = 200;
+
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct ScoreLine {
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    // points per game in percent, a draw counts half
+    pub score: f32,
+}
+
+impl ScoreLine {
+    fn add(&mut self, points: Points) {
+        self.games += 1;
+        match points {
+            Points::Win => self.wins += 1,
+            Points::Draw => self.draws += 1,
+            Points::Loss => self.losses += 1,
+        }
+        self.score = (self.wins as f32 + self.draws as f32 / 2.0) / self.games as f32 * 100.0;
+    }
+}
+
+/// Score for one opening, time class, rating band or termination.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct GroupScore {
+    pub key: String,
+    // opening name for ECO groups
+    pub name: Option<String>,
+    pub score: ScoreLine,
+}
+
+/// Longest and current runs of results, oldest game first.
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct Streaks {
+    pub longest_win: u32,
+    pub longest_loss: u32,
+    pub longest_unbeaten: u32,
+    pub current_win: u32,
+    pub current_loss: u32,
+    pub current_unbeaten: u32,
+}
+
+/// Results of one calendar month.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct PeriodStats {
+    // "YYYY-MM"
+    pub period: String,
+    pub score: ScoreLine,
+    // player's own average rating, None when no game was rated
+    pub average_elo: Option<u32>,
+    pub average_opponent_elo: Option<u32>,
+    // average opponent rating + 400 * (wins - losses) / games
+    pub performance: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct PlayerStats {
+    pub player: String,
+    pub overall: ScoreLine,
+    pub as_white: ScoreLine,
+    pub as_black: ScoreLine,
+    // most played first
+    pub by_eco: Vec<GroupScore>,
+    // bullet, blitz, rapid, classical
+    pub by_time_class: Vec<GroupScore>,
+    // "1400-1599", lowest first; "unrated" last
+    pub by_opponent_rating: Vec<GroupScore>,
+    // checkmate, resignation, timeout, ...; most frequent first
+    pub by_termination: Vec<GroupScore>,
+    pub streaks: Streaks,
+    // full moves per finished game
+    pub average_moves: f32,
+    // unfinished games are left out of every score
+    pub unfinished: u32,
+    pub over_time: Vec<PeriodStats>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Points {
+    Win,
+    Draw,
+    Loss,
+}
+
+// one finished game from the player's side
+struct PlayedGame {
+    color: PieceColor,
+    points: Points,
+    elo: u32,
+    opponent_elo: u32,
+    eco: Option<String>,
+    opening: Option<String>,
+    time_class: Option<String>,
+    played_on: Option<String>,
+    termination: TerminationReason,
+    plies: u32,
+}
+
+fn group(groups: &mut Vec<GroupScore>, key: &str, name: Option<&String>, points: Points) {
+    let index = match groups.iter().position(|g| g.key == key) {
+        Some(index) => index,
+        None => {
+            groups.push(GroupScore {
+                key: key.to_string(),
+                name: name.cloned(),
+                score: ScoreLine::default(),
+            });
+            groups.len() - 1
+        }
+    };
+    groups[index].score.add(points);
+}
+
+fn rating_band(elo: u32) -> String {
+    if elo == 0 {
+        return "unrated".to_string();
+    }
+    let low = elo / RATING_BAND * RATING_BAND;
+    format!("{}-{}", low, low + RATING_BAND - 1)
+}
+
+fn average(values: &[u32]) -> Option<u32> {
+    let rated: Vec<u32> = values.iter().copied().filter(|v| *v > 0).collect();
+    if rated.is_empty() {
+        return None;
+    }
+    Some(rated.iter().sum::<u32>() / rated.len() as u32)
+}
+
+fn period_stats(period: String, games: &[&PlayedGame]) -> PeriodStats {
+    let mut score = ScoreLine::default();
+    for game in games {
+        score.add(game.points);
+    }
+    let average_elo = average(&games.iter().map(|g| g.elo).collect::<Vec<_>>());
+    let opponents: Vec<&&PlayedGame> = games.iter().filter(|g| g.opponent_elo > 0).collect();
+    let average_opponent_elo =
+        average(&opponents.iter().map(|g| g.opponent_elo).collect::<Vec<_>>());
+    let performance = average_opponent_elo.map(|opponent| {
+        let net: i64 = opponents
+            .iter()
+            .map(|g| match g.points {
+                Points::Win => 1,
+                Points::Draw => 0,
+                Points::Loss => -1,
+            })
+            .sum();
+        (opponent as i64 + 400 * net / opponents.len() as i64).max(0) as u32
+    });
+    PeriodStats {
+        period,
+        score,
+        average_elo,
+        average_opponent_elo,
+        performance,
+    }
+}
+
+// How the game ended, from its final position when that decides it and from
+// the Termination tag otherwise. Games used to be stored with a "draw"
+// termination whatever the result, so a decisive draw is unknown.
+fn termination(pgn_data: &str, points: Points) -> TerminationReason {
+    let Ok(metadata) = parse_pgn(pgn_data) else {
+        return TerminationReason::Unknown;
+    };
+    if let Some(fen) = mainline_fens(&metadata).pop() {
+        let mut board = Board::from(&fen);
+        board.rerender_move_cache();
+        if board.is_checkmate() {
+            return TerminationReason::Checkmate;
+        }
+        if board.is_stalemate() {
+            return TerminationReason::StaleMate;
+        }
+    }
+    match metadata.termination {
+        TerminationReason::Draw if points != Points::Draw => TerminationReason::Unknown,
+        reason => reason,
+    }
+}
+
+fn termination_key(reason: &TerminationReason) -> &'static str {
+    match reason {
+        TerminationReason::Checkmate => "checkmate",
+        TerminationReason::StaleMate => "stalemate",
+        TerminationReason::Draw => "draw",
+        TerminationReason::Timeout => "timeout",
+        TerminationReason::Resignation => "resignation",
+        TerminationReason::Unknown => "unknown",
+    }
+}
+
+fn load_games(
+    con: &Connection,
+    player: &str,
+    filter: &GameFilter,
+) -> rusqlite::Result<(Vec<PlayedGame>, u32)> {
+    // the filter's player conditions (color, rating) should refer to this player
+    let filter = GameFilter {
+        player: Some(player.to_string()),
+        ..filter.clone()
+    };
+    let (condition, filter_values) = filter.to_sql();
+    let mut values = vec![
+        Value::Text(player.to_string()),
+        Value::Text(player.to_string()),
+    ];
+    values.extend(filter_values);
+    let mut stmt = con.prepare(&format!(
+        "SELECT white_player, white_elo, black_elo, result, eco, opening, time_class, played_on,
+            pgn_data, (SELECT MAX(ply) FROM positions p WHERE p.game_id = games.game_id)
+         FROM games
+         WHERE (white_player = ? COLLATE NOCASE OR black_player = ? COLLATE NOCASE) AND {}
+         ORDER BY played_on, game_id",
+        condition
+    ))?;
+    let mut rows = stmt.query(params_from_iter(values))?;
+
+    let mut games = Vec::new();
+    let mut unfinished = 0;
+    while let Some(row) = rows.next()? {
+        let white: String = row.get(0)?;
+        let color = if white.eq_ignore_ascii_case(player) {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        if filter.color.is_some_and(|c| c != color) {
+            continue;
+        }
+        let white_elo = row.get::<_, Option<u32>>(1)?.unwrap_or(0);
+        let black_elo = row.get::<_, Option<u32>>(2)?.unwrap_or(0);
+        let result: String = row.get(3)?;
+        let points = match (result.as_str(), color) {
+            ("1/2-1/2", _) => Points::Draw,
+            ("1-0", PieceColor::White) | ("0-1", PieceColor::Black) => Points::Win,
+            ("1-0", PieceColor::Black) | ("0-1", PieceColor::White) => Points::Loss,
+            _ => {
+                unfinished += 1;
+                continue;
+            }
+        };
+        let (elo, opponent_elo) = match color {
+            PieceColor::White => (white_elo, black_elo),
+            PieceColor::Black => (black_elo, white_elo),
+        };
+        let pgn_data: String = row.get(8)?;
+        games.push(PlayedGame {
+            color,
+            points,
+            elo,
+            opponent_elo,
+            eco: row.get::<_, Option<String>>(4)?.filter(|e| !e.is_empty()),
+            opening: row.get::<_, Option<String>>(5)?.filter(|o| !o.is_empty()),
+            time_class: row.get(6)?,
+            played_on: row.get(7)?,
+            termination: termination(&pgn_data, points),
+            plies: row.get::<_, Option<u32>>(9)?.unwrap_or(0),
+        });
+    }
+    Ok((games, unfinished))
+}
+
+/// Dashboard numbers for `player` (exact name, any case) over the games
+/// matching `filter`.
+pub fn player_stats(
+    con: &Connection,
+    player: &str,
+    filter: &GameFilter,
+) -> rusqlite::Result<PlayerStats> {
+    let (games, unfinished) = load_games(con, player, filter)?;
+    let mut stats = PlayerStats {
+        player: player.to_string(),
+        unfinished,
+        ..Default::default()
+    };
+
+    let mut streak = (0, 0, 0);
+    for game in &games {
+        stats.overall.add(game.points);
+        match game.color {
+            PieceColor::White => stats.as_white.add(game.points),
+            PieceColor::Black => stats.as_black.add(game.points),
+        }
+        let eco = game.eco.as_deref().unwrap_or("?");
+        group(&mut stats.by_eco, eco, game.opening.as_ref(), game.points);
+        let time_class = game.time_class.as_deref().unwrap_or("unknown");
+        group(&mut stats.by_time_class, time_class, None, game.points);
+        let band = rating_band(game.opponent_elo);
+        group(&mut stats.by_opponent_rating, &band, None, game.points);
+        let termination = termination_key(&game.termination);
+        group(&mut stats.by_termination, termination, None, game.points);
+
+        // (wins, losses, unbeaten) in a row up to this game
+        streak = match game.points {
+            Points::Win => (streak.0 + 1, 0, streak.2 + 1),
+            Points::Draw => (0, 0, streak.2 + 1),
+            Points::Loss => (0, streak.1 + 1, 0),
+        };
+        stats.streaks.longest_win = stats.streaks.longest_win.max(streak.0);
+        stats.streaks.longest_loss = stats.streaks.longest_loss.max(streak.1);
+        stats.streaks.longest_unbeaten = stats.streaks.longest_unbeaten.max(streak.2);
+    }
+    (
+        stats.streaks.current_win,
+        stats.streaks.current_loss,
+        stats.streaks.current_unbeaten,
+    ) = streak;
+
+    if !games.is_empty() {
+        let plies: u32 = games.iter().map(|g| g.plies).sum();
+        stats.average_moves = plies as f32 / 2.0 / games.len() as f32;
+    }
+
+    stats
+        .by_eco
+        .sort_by(|a, b| b.score.games.cmp(&a.score.games).then(a.key.cmp(&b.key)));
+    let class_order = |key: &str| {
+        ["bullet", "blitz", "rapid", "classical"]
+            .iter()
+            .position(|c| *c == key)
+            .unwrap_or(usize::MAX)
+    };
+    stats.by_time_class.sort_by_key(|g| class_order(&g.key));
+    stats.by_opponent_rating.sort_by_key(|g| {
+        g.key
+            .split('-')
+            .next()
+            .and_then(|low| low.parse::<u32>().ok())
+            .unwrap_or(u32::MAX)
+    });
+    stats
+        .by_termination
+        .sort_by(|a, b| b.score.games.cmp(&a.score.games).then(a.key.cmp(&b.key)));
+
+    // games come sorted by date, so each month is one run
+    let mut months: Vec<(String, Vec<&PlayedGame>)> = Vec::new();
+    for game in &games {
+        let Some(month) = game.played_on.as_deref().and_then(|d| d.get(..7)) else {
+            continue;
+        };
+        match months.last_mut() {
+            Some((last, month_games)) if last == month => month_games.push(game),
+            _ => months.push((month.to_string(), vec![game])),
+        }
+    }
+    stats.over_time = months
+        .into_iter()
+        .map(|(month, games)| period_stats(month, &games))
+        .collect();
+    Ok(stats)
+}
+
+/// Personal dashboard: scores by color, opening, time class, opponent rating
+/// and termination, streaks, game length and month by month results.
+#[tauri::command]
+pub fn get_player_stats(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    player: String,
+    filters: Option<GameFilter>,
+) -> Result<PlayerStats, String> {
+    let player = player.trim();
+    if player.is_empty() {
+        return Err("No player given".to_string());
+    }
+    let con = state_connection(&state)?;
+    player_stats(&con, player, &filters.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn termination_from_the_final_position() {
+        let mate = "1. f3 e5 2. g4 Qh4# 0-1";
+        assert!(matches!(
+            termination(mate, Points::Loss),
+            TerminationReason::Checkmate
+        ));
+
+        // mates in variations and comments decide nothing
+        let resigned = "[Termination \"Black won by resignation\"]\n\n\
+            1. f3 e5 2. g4 (2. Nc3 { not 2. g4 Qh4# }) Nc6 0-1";
+        assert!(matches!(
+            termination(resigned, Points::Loss),
+            TerminationReason::Resignation
+        ));
+
+        let untagged = "[Termination \"draw\"]\n\n1. e4 e5 1-0";
+        assert!(matches!(
+            termination(untagged, Points::Win),
+            TerminationReason::Unknown
+        ));
+        assert_eq!(termination_key(&TerminationReason::Unknown), "unknown");
+    }
+}
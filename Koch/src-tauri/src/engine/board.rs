@@ -1,5 +1,8 @@
 use crate::{
-    engine::{fen::fen_parser, move_gen::MoveError, ChessPiece, PieceColor, PieceType},
+    engine::{
+        fen::fen_parser, move_gen::MoveError, move_tree::MoveTree, ChessPiece, PieceColor,
+        PieceType,
+    },
     etc::{DEFAULT_FEN, DEFAULT_STARTING},
     game::controller::TerminationReason,
 };
@@ -108,7 +111,7 @@ impl ToString for GameResult {
 pub fn parse_game_result(s: &str) -> GameResult {
     GameResult::from(s)
 }
-#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub enum GamePhase {
     Opening,
@@ -149,6 +152,12 @@ pub struct BoardMetaData {
     pub end_time: Option<String>,
     pub link: Option<String>,
     pub eco: Option<String>,
+    // tags without a dedicated field, kept in the order they were read
+    pub extra_tags: Vec<(String, String)>,
+    // full game including variations; `move_list` mirrors its main line
+    pub move_tree: MoveTree,
+    // row id when the game was read from the database
+    pub game_id: Option<u32>,
 }
 
 #[derive(Clone, TS, Serialize, Deserialize)]
@@ -196,7 +205,7 @@ impl Default for BoardMetaData {
             starting_position: DEFAULT_FEN.to_string(),
             date: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
             move_list: Vec::new(),
-            termination: TerminationReason::Draw,
+            termination: TerminationReason::Unknown,
             result: GameResult::Unfinished,
             white_player_elo: 0,
             black_player_elo: 0,
@@ -210,6 +219,9 @@ impl Default for BoardMetaData {
             end_time: None,
             eco: None,
             link: None,
+            extra_tags: Vec::new(),
+            move_tree: MoveTree::new(DEFAULT_FEN),
+            game_id: None,
         }
     }
 }
@@ -227,6 +239,8 @@ pub struct MoveStruct {
     pub nag: Option<i32>,
     pub time_stamp: Option<u32>,
     pub clock: Option<String>,
+    // engine evaluation after the move, from a [%eval] comment or analysis
+    pub eval: Option<EvalResponse>,
 }
 impl Default for MoveStruct {
     fn default() -> Self {
@@ -240,6 +254,7 @@ impl Default for MoveStruct {
             nag: None,
             time_stamp: None,
             clock: None,
+            eval: None,
         }
     }
 }
@@ -354,6 +369,7 @@ impl Board {
                 nag: None,
                 time_stamp: None,
                 clock: None,
+                eval: None,
             };
 
             return Ok(mv);
@@ -494,6 +510,7 @@ impl Board {
             nag: None,
             time_stamp: None,
             clock: None,
+            eval: None,
         })
     }
 
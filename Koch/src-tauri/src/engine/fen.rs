@@ -373,3 +373,23 @@ pub fn make_empty_cols(count: usize) -> Vec<String> {
     let white_spaces = vec![".".to_string(); count];
     white_spaces
 }
+
+/// FEN without the halfmove clock and fullmove number, so the same position
+/// reached at different points of a game compares equal. The en passant field
+/// is always "-" since `Board::to_string` never writes one either.
+pub fn position_key(fen: &str) -> String {
+    let mut parts: Vec<&str> = fen.split_whitespace().take(3).collect();
+    parts.push("-");
+    parts.join(" ")
+}
+
+/// Stable 64-bit FNV-1a hash of `position_key`. It is stored in the database,
+/// so it must not depend on the std hasher, which may change between releases.
+pub fn position_hash(fen: &str) -> i64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in position_key(fen).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash as i64
+}
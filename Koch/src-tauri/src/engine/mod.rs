@@ -2,6 +2,8 @@ pub mod board;
 pub mod capture;
 pub mod fen;
 pub mod move_gen;
+pub mod move_tree;
+pub mod pgn;
 pub mod piece;
 pub mod quiet;
 pub mod san;
@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use ts_rs::TS;
+
+use crate::engine::board::MoveStruct;
+
+pub const ROOT_NODE: usize = 0;
+
+/// One position in a game tree.
+/// The root node has no move and stands for the starting position; every other
+/// node holds the move that led to it. `children[0]` is the main continuation,
+/// the remaining children are variations in the order they were added.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct MoveNode {
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub mv: Option<MoveStruct>,
+    // FEN of the position after `mv`
+    pub fen: String,
+    // comments written before the move (only meaningful at the start of a variation)
+    pub pre_comments: Vec<String>,
+    pub comments: Vec<String>,
+    pub nags: Vec<u8>,
+}
+
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct MoveTree {
+    pub nodes: HashMap<usize, MoveNode>,
+    pub next_id: usize,
+}
+
+impl MoveTree {
+    pub fn new(starting_fen: &str) -> Self {
+        let root = MoveNode {
+            id: ROOT_NODE,
+            parent: None,
+            children: Vec::new(),
+            mv: None,
+            fen: starting_fen.to_string(),
+            pre_comments: Vec::new(),
+            comments: Vec::new(),
+            nags: Vec::new(),
+        };
+        let mut nodes = HashMap::new();
+        nodes.insert(ROOT_NODE, root);
+        Self { nodes, next_id: 1 }
+    }
+
+    /// Build a single-line tree from a flat move list. Positions are not
+    /// replayed, so node FENs are left empty.
+    pub fn from_move_list(starting_fen: &str, moves: &[MoveStruct]) -> Self {
+        let mut tree = Self::new(starting_fen);
+        let mut parent = ROOT_NODE;
+        for mv in moves {
+            let Some(id) = tree.add_move(parent, mv.clone(), String::new()) else {
+                break;
+            };
+            if let Some(node) = tree.get_mut(id) {
+                if let Some(annotation) = mv.annotation.as_ref() {
+                    node.comments.push(annotation.clone());
+                }
+                if let Some(nag) = mv.nag {
+                    node.nags.push(nag as u8);
+                }
+            }
+            parent = id;
+        }
+        tree
+    }
+
+    pub fn get(&self, id: usize) -> Option<&MoveNode> {
+        self.nodes.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut MoveNode> {
+        self.nodes.get_mut(&id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes
+            .get(&ROOT_NODE)
+            .map(|root| root.children.is_empty())
+            .unwrap_or(true)
+    }
+
+    /// Append `mv` as a child of `parent`. If the same move already exists there
+    /// the existing node is returned instead of creating a duplicate variation.
+    pub fn add_move(&mut self, parent: usize, mv: MoveStruct, fen: String) -> Option<usize> {
+        let existing = self.get(parent)?.children.iter().copied().find(|child| {
+            self.get(*child)
+                .and_then(|node| node.mv.as_ref())
+                .is_some_and(|child_mv| child_mv.uci == mv.uci)
+        });
+        if let Some(existing) = existing {
+            return Some(existing);
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.nodes.insert(
+            id,
+            MoveNode {
+                id,
+                parent: Some(parent),
+                children: Vec::new(),
+                mv: Some(mv),
+                fen,
+                pre_comments: Vec::new(),
+                comments: Vec::new(),
+                nags: Vec::new(),
+            },
+        );
+        self.get_mut(parent)?.children.push(id);
+        Some(id)
+    }
+
+    /// Node ids of the main line, root excluded.
+    pub fn mainline(&self) -> Vec<usize> {
+        let mut line = Vec::new();
+        let mut current = ROOT_NODE;
+        while let Some(next) = self.get(current).and_then(|node| node.children.first()) {
+            line.push(*next);
+            current = *next;
+        }
+        line
+    }
+
+    /// Main line as a flat move list, with the node comments and first NAG
+    /// folded back into each `MoveStruct`.
+    pub fn mainline_moves(&self) -> Vec<MoveStruct> {
+        self.mainline()
+            .iter()
+            .filter_map(|id| self.move_with_annotations(*id))
+            .collect()
+    }
+
+    pub fn move_with_annotations(&self, id: usize) -> Option<MoveStruct> {
+        let node = self.get(id)?;
+        let mut mv = node.mv.clone()?;
+        if !node.comments.is_empty() {
+            mv.annotation = Some(node.comments.join(" "));
+        }
+        mv.nag = node.nags.first().map(|nag| *nag as i32);
+        Some(mv)
+    }
+
+    /// Node ids from the first move up to and including `id`.
+    pub fn path_to(&self, id: usize) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut current = Some(id);
+        while let Some(node_id) = current {
+            if node_id == ROOT_NODE {
+                break;
+            }
+            let Some(node) = self.get(node_id) else {
+                return Vec::new();
+            };
+            path.push(node_id);
+            current = node.parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Number of half-moves between the root and `id`.
+    pub fn depth(&self, id: usize) -> usize {
+        self.path_to(id).len()
+    }
+
+    /// Sibling of `id` `offset` places away in its parent's children.
+    pub fn sibling(&self, id: usize, offset: isize) -> Option<usize> {
+        let parent = self.get(self.get(id)?.parent?)?;
+        let index = parent.children.iter().position(|child| *child == id)? as isize;
+        let target = index + offset;
+        if target < 0 {
+            return None;
+        }
+        parent.children.get(target as usize).copied()
+    }
+
+    /// Move `id` `offset` places among its siblings; index 0 is the main line.
+    pub fn shift_variation(&mut self, id: usize, offset: isize) -> bool {
+        let Some(parent) = self.get(id).and_then(|node| node.parent) else {
+            return false;
+        };
+        let Some(parent) = self.get_mut(parent) else {
+            return false;
+        };
+        let Some(index) = parent.children.iter().position(|child| *child == id) else {
+            return false;
+        };
+        let target = index as isize + offset;
+        if target < 0 || target >= parent.children.len() as isize {
+            return false;
+        }
+        let child = parent.children.remove(index);
+        parent.children.insert(target as usize, child);
+        true
+    }
+
+    /// Remove `id` and everything after it. The root can't be removed.
+    pub fn remove_subtree(&mut self, id: usize) -> bool {
+        let Some(parent) = self.get(id).and_then(|node| node.parent) else {
+            return false;
+        };
+        if let Some(parent) = self.get_mut(parent) {
+            parent.children.retain(|child| *child != id);
+        }
+        let mut stack = vec![id];
+        while let Some(node_id) = stack.pop() {
+            if let Some(node) = self.nodes.remove(&node_id) {
+                stack.extend(node.children);
+            }
+        }
+        true
+    }
+
+    /// True if `ancestor` lies on the path from the root to `id` (inclusive).
+    pub fn is_ancestor(&self, ancestor: usize, id: usize) -> bool {
+        ancestor == ROOT_NODE || self.path_to(id).contains(&ancestor)
+    }
+}
@@ -0,0 +1,892 @@
+use std::fmt::{self, Write};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    engine::{
+        board::{BoardMetaData, EvalResponse, EvalType, GameResult, MoveStruct},
+        fen::fen_parser,
+        move_tree::{MoveTree, ROOT_NODE},
+        Board,
+    },
+    game::controller::TerminationReason,
+};
+
+/// Date tag value of a game played on an unknown day.
+pub const UNKNOWN_DATE: &str = "????.??.??";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PgnToken {
+    TagOpen,
+    TagClose,
+    Str(String),
+    // move text, move numbers, tag names and game results ("1-0", "1/2-1/2")
+    Symbol(String),
+    Period,
+    Asterisk,
+    Comment(String),
+    VariationOpen,
+    VariationClose,
+    Nag(u8),
+}
+
+#[derive(Clone, Debug)]
+pub enum PgnError {
+    UnterminatedString { line: usize },
+    UnterminatedComment { line: usize },
+    UnexpectedToken { line: usize, token: String },
+    UnbalancedVariation { line: usize },
+    IllegalMove { line: usize, ply: usize, san: String },
+    InvalidFen(String),
+    NoGame,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::UnterminatedString { line } => {
+                write!(f, "line {}: unterminated tag string", line)
+            }
+            PgnError::UnterminatedComment { line } => {
+                write!(f, "line {}: unterminated {{ comment", line)
+            }
+            PgnError::UnexpectedToken { line, token } => {
+                write!(f, "line {}: unexpected token '{}'", line, token)
+            }
+            PgnError::UnbalancedVariation { line } => {
+                write!(f, "line {}: unbalanced variation parentheses", line)
+            }
+            PgnError::IllegalMove { line, ply, san } => {
+                write!(f, "line {}: illegal move '{}' at ply {}", line, san, ply)
+            }
+            PgnError::InvalidFen(fen) => write!(f, "invalid FEN tag '{}'", fen),
+            PgnError::NoGame => write!(f, "no game found"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// Split PGN text into tokens, each paired with the line it started on.
+/// Escape lines (starting with '%'), `;` rest-of-line comments and `{}` comments
+/// are handled here; braces inside a comment are allowed to nest.
+pub fn tokenize(input: &str) -> Result<Vec<(PgnToken, usize)>, PgnError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut at_line_start = true;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if at_line_start && c == '%' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        at_line_start = false;
+
+        match c {
+            '\n' => {
+                line += 1;
+                at_line_start = true;
+                i += 1;
+            }
+            c if c.is_whitespace() => i += 1,
+            '[' => {
+                tokens.push((PgnToken::TagOpen, line));
+                i += 1;
+            }
+            ']' => {
+                tokens.push((PgnToken::TagClose, line));
+                i += 1;
+            }
+            '(' => {
+                tokens.push((PgnToken::VariationOpen, line));
+                i += 1;
+            }
+            ')' => {
+                tokens.push((PgnToken::VariationClose, line));
+                i += 1;
+            }
+            '.' => {
+                tokens.push((PgnToken::Period, line));
+                i += 1;
+            }
+            '*' => {
+                tokens.push((PgnToken::Asterisk, line));
+                i += 1;
+            }
+            '"' => {
+                let start_line = line;
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(PgnError::UnterminatedString { line: start_line }),
+                        Some('\\') if matches!(chars.get(i + 1), Some('"') | Some('\\')) => {
+                            value.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(ch) => {
+                            if *ch == '\n' {
+                                line += 1;
+                            }
+                            value.push(*ch);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push((PgnToken::Str(value), start_line));
+            }
+            '{' => {
+                let start_line = line;
+                let mut depth = 1;
+                let mut text = String::new();
+                i += 1;
+                while depth > 0 {
+                    match chars.get(i) {
+                        None => return Err(PgnError::UnterminatedComment { line: start_line }),
+                        Some('{') => {
+                            depth += 1;
+                            text.push('{');
+                        }
+                        Some('}') => {
+                            depth -= 1;
+                            if depth > 0 {
+                                text.push('}');
+                            }
+                        }
+                        Some('\n') => {
+                            line += 1;
+                            text.push(' ');
+                        }
+                        Some(ch) => text.push(*ch),
+                    }
+                    i += 1;
+                }
+                tokens.push((PgnToken::Comment(text.trim().to_string()), start_line));
+            }
+            ';' => {
+                let mut text = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != '\n' {
+                    text.push(chars[i]);
+                    i += 1;
+                }
+                tokens.push((PgnToken::Comment(text.trim().to_string()), line));
+            }
+            '$' => {
+                let mut digits = String::new();
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    digits.push(chars[i]);
+                    i += 1;
+                }
+                match digits.parse::<u8>() {
+                    Ok(nag) => tokens.push((PgnToken::Nag(nag), line)),
+                    Err(_) => {
+                        return Err(PgnError::UnexpectedToken {
+                            line,
+                            token: format!("${}", digits),
+                        })
+                    }
+                }
+            }
+            '!' | '?' => {
+                let mut suffix = String::new();
+                while i < chars.len() && matches!(chars[i], '!' | '?') {
+                    suffix.push(chars[i]);
+                    i += 1;
+                }
+                match suffix_to_nag(&suffix) {
+                    Some(nag) => tokens.push((PgnToken::Nag(nag), line)),
+                    None => return Err(PgnError::UnexpectedToken { line, token: suffix }),
+                }
+            }
+            '<' => {
+                // reserved for future expansion by the standard, skip the whole group
+                while i < chars.len() && chars[i] != '>' {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            c if c.is_ascii_alphanumeric() => {
+                let mut symbol = String::new();
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric() || "_+#=:-/".contains(chars[i]))
+                {
+                    symbol.push(chars[i]);
+                    i += 1;
+                }
+                tokens.push((PgnToken::Symbol(symbol), line));
+            }
+            other => {
+                return Err(PgnError::UnexpectedToken {
+                    line,
+                    token: other.to_string(),
+                })
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn suffix_to_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+fn is_result_symbol(s: &str) -> bool {
+    matches!(s, "1-0" | "0-1" | "1/2-1/2")
+}
+
+// Helper: parse termination string into TerminationReason
+pub fn parse_termination(s: &str) -> TerminationReason {
+    let s_l = s.to_lowercase();
+    // "stalemate" contains "mate", check it first
+    if s_l.contains("stalemate") || s_l.contains("stale") {
+        TerminationReason::StaleMate
+    } else if s_l.contains("checkmate") || s_l.contains("mate") {
+        TerminationReason::Checkmate
+    } else if s_l.contains("resign") {
+        TerminationReason::Resignation
+    } else if s_l.contains("timeout") || s_l.contains("time forfeit") || s_l.contains("on time") {
+        TerminationReason::Timeout
+    } else if s_l.contains("draw") {
+        TerminationReason::Draw
+    } else {
+        TerminationReason::Unknown
+    }
+}
+
+/// Store a tag on the metadata. Returns false for tags `BoardMetaData` has no
+/// field for, so the caller can keep them as extra tags.
+pub fn set_tag(metadata: &mut BoardMetaData, tag: &str, value: &str) -> bool {
+    let value = value.to_string();
+    match tag {
+        "Event" => metadata.event = Some(value),
+        "Site" => metadata.site = Some(value),
+        "Date" => metadata.date = value,
+        "Round" => metadata.round = Some(value),
+        "White" => metadata.white_player_name = value,
+        "Black" => metadata.black_player_name = value,
+        "Result" => metadata.result = GameResult::from(value.as_str()),
+        "WhiteElo" => metadata.white_player_elo = value.parse::<u32>().unwrap_or(0),
+        "BlackElo" => metadata.black_player_elo = value.parse::<u32>().unwrap_or(0),
+        "TimeControl" => metadata.time_control = Some(value),
+        "Termination" => metadata.termination = parse_termination(&value),
+        "ECO" => metadata.eco = Some(value),
+        "Opening" => metadata.opening = Some(value),
+        "EndTime" => metadata.end_time = Some(value),
+        "Link" => metadata.link = Some(value),
+        _ => return false,
+    }
+    true
+}
+
+/// Value of a tag as written in the PGN text, without parsing the game.
+pub fn find_tag<'a>(pgn: &'a str, name: &str) -> Option<&'a str> {
+    let prefix = format!("[{} \"", name);
+    pgn.lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix(&prefix)?.strip_suffix("\"]"))
+}
+
+/// Accept the 4-field EPD-style FEN some tools write and make sure the
+/// numeric fields parse before handing it to `fen_parser`, which panics on them.
+fn normalize_fen(fen: &str) -> Result<String, PgnError> {
+    let parts: Vec<&str> = fen.split_whitespace().collect();
+    let fen = match parts.len() {
+        4 => format!("{} 0 1", parts.join(" ")),
+        6 => parts.join(" "),
+        _ => return Err(PgnError::InvalidFen(fen.to_string())),
+    };
+    let parts: Vec<&str> = fen.split_whitespace().collect();
+    if parts[0].split('/').count() != 8
+        || !matches!(parts[1], "w" | "b")
+        || parts[4].parse::<u32>().is_err()
+        || parts[5].parse::<u32>().is_err()
+    {
+        return Err(PgnError::InvalidFen(fen));
+    }
+    Ok(fen)
+}
+
+fn board_from_fen(fen: &String) -> Result<Board, PgnError> {
+    let mut board = fen_parser(fen).map_err(|_| PgnError::InvalidFen(fen.clone()))?;
+    board.rerender_move_cache();
+    Ok(board)
+}
+
+/// Pull `[%clk ...]`-style embedded commands out of a comment.
+/// Returns the commands as (name, value) pairs and the remaining text.
+pub fn split_comment_commands(text: &str) -> (Vec<(String, String)>, String) {
+    let mut commands = Vec::new();
+    let mut rest = String::new();
+    let mut remaining = text;
+    while let Some(start) = remaining.find("[%") {
+        rest.push_str(&remaining[..start]);
+        let after = &remaining[start + 2..];
+        match after.find(']') {
+            Some(end) => {
+                let body = after[..end].trim();
+                let (name, value) = body.split_once(char::is_whitespace).unwrap_or((body, ""));
+                commands.push((name.to_string(), value.trim().to_string()));
+                remaining = &after[end + 1..];
+            }
+            None => {
+                rest.push_str(&remaining[start..]);
+                remaining = "";
+            }
+        }
+    }
+    rest.push_str(remaining);
+    let rest = rest.split_whitespace().collect::<Vec<_>>().join(" ");
+    (commands, rest)
+}
+
+fn apply_comment(tree: &mut MoveTree, node_id: usize, text: &str) {
+    let Some(node) = tree.get_mut(node_id) else {
+        return;
+    };
+    let (commands, mut rest) = split_comment_commands(text);
+    let mut unknown = String::new();
+    for (name, value) in commands {
+        match (name.as_str(), node.mv.as_mut()) {
+            ("clk", Some(mv)) => mv.clock = Some(value),
+            ("timestamp", Some(mv)) => mv.time_stamp = value.parse::<u32>().ok(),
+            ("eval", Some(mv)) if parse_eval_command(&value).is_some() => {
+                mv.eval = parse_eval_command(&value)
+            }
+            _ => write!(unknown, "[%{} {}]", name, value).unwrap(),
+        }
+    }
+    if !unknown.is_empty() {
+        rest = if rest.is_empty() {
+            unknown
+        } else {
+            format!("{} {}", unknown, rest)
+        };
+    }
+    if !rest.is_empty() {
+        node.comments.push(rest);
+    }
+}
+
+struct Frame {
+    node: usize,
+    ply: usize,
+    board: Board,
+    // position before the move at `node`, where a variation on that move starts from
+    prev_board: Option<Board>,
+    has_moves: bool,
+    pending_comments: Vec<String>,
+}
+
+/// Parse a single PGN game (tag pairs + movetext) into metadata and a move tree.
+/// `move_list` is filled with the main line so callers that only know about
+/// flat games keep working.
+pub fn parse_pgn(input: &str) -> Result<BoardMetaData, PgnError> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err(PgnError::NoGame);
+    }
+    let mut pos = 0;
+    let mut metadata = BoardMetaData {
+        // a game without a Date tag was not played today
+        date: UNKNOWN_DATE.to_string(),
+        ..Default::default()
+    };
+
+    // Tag pair section
+    let mut fen_tag: Option<String> = None;
+    let mut setup_tag: Option<String> = None;
+    let mut has_result_tag = false;
+    while let Some((PgnToken::TagOpen, line)) = tokens.get(pos) {
+        match (tokens.get(pos + 1), tokens.get(pos + 2), tokens.get(pos + 3)) {
+            (
+                Some((PgnToken::Symbol(name), _)),
+                Some((PgnToken::Str(value), _)),
+                Some((PgnToken::TagClose, _)),
+            ) => match name.as_str() {
+                "FEN" => fen_tag = Some(value.clone()),
+                "SetUp" => setup_tag = Some(value.clone()),
+                _ => {
+                    has_result_tag |= name == "Result";
+                    if !set_tag(&mut metadata, name, value) {
+                        metadata.extra_tags.push((name.clone(), value.clone()));
+                    }
+                }
+            },
+            _ => {
+                return Err(PgnError::UnexpectedToken {
+                    line: *line,
+                    token: "[".to_string(),
+                })
+            }
+        }
+        pos += 4;
+    }
+
+    if let Some(fen) = fen_tag {
+        if setup_tag.as_deref() != Some("0") {
+            metadata.starting_position = normalize_fen(&fen)?;
+        }
+    }
+    let start_board = board_from_fen(&metadata.starting_position)?;
+    let mut tree = MoveTree::new(&metadata.starting_position);
+
+    // Movetext section
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut frame = Frame {
+        node: ROOT_NODE,
+        ply: 0,
+        board: start_board,
+        prev_board: None,
+        has_moves: false,
+        pending_comments: Vec::new(),
+    };
+    let mut game_termination: Option<GameResult> = None;
+
+    while let Some((token, line)) = tokens.get(pos) {
+        let line = *line;
+        pos += 1;
+        match token {
+            PgnToken::Asterisk => {
+                if !stack.is_empty() {
+                    return Err(PgnError::UnbalancedVariation { line });
+                }
+                game_termination = Some(GameResult::Unfinished);
+                break;
+            }
+            PgnToken::Symbol(s) if is_result_symbol(s) => {
+                if !stack.is_empty() {
+                    return Err(PgnError::UnbalancedVariation { line });
+                }
+                game_termination = Some(GameResult::from(s.as_str()));
+                break;
+            }
+            // move numbers
+            PgnToken::Symbol(s) if s.chars().all(|c| c.is_ascii_digit()) => {}
+            PgnToken::Period => {}
+            PgnToken::Symbol(san) => {
+                let ply = frame.ply + 1;
+                let illegal = || PgnError::IllegalMove {
+                    line,
+                    ply,
+                    san: san.clone(),
+                };
+                let (from, to, promotion) =
+                    frame.board.resolve_san(san).map_err(|_| illegal())?;
+                let before = frame.board.clone();
+                let mut mv = frame
+                    .board
+                    .move_piece(from, to, promotion)
+                    .map_err(|_| illegal())?;
+                mv.san = san.clone();
+                mv.move_number = ply as u32;
+                let fen = frame.board.to_string();
+                let id = tree.add_move(frame.node, mv, fen).ok_or_else(illegal)?;
+                if !frame.pending_comments.is_empty() {
+                    if let Some(node) = tree.get_mut(id) {
+                        node.pre_comments = std::mem::take(&mut frame.pending_comments);
+                    }
+                }
+                frame.prev_board = Some(before);
+                frame.node = id;
+                frame.ply = ply;
+                frame.has_moves = true;
+            }
+            PgnToken::Nag(nag) => {
+                if frame.has_moves {
+                    if let Some(node) = tree.get_mut(frame.node) {
+                        node.nags.push(*nag);
+                    }
+                }
+            }
+            PgnToken::Comment(text) => {
+                if text.is_empty() {
+                    continue;
+                }
+                if frame.has_moves {
+                    apply_comment(&mut tree, frame.node, text);
+                } else if stack.is_empty() {
+                    apply_comment(&mut tree, ROOT_NODE, text);
+                } else {
+                    frame.pending_comments.push(text.clone());
+                }
+            }
+            PgnToken::VariationOpen => {
+                let (Some(prev_board), Some(parent)) = (
+                    frame.prev_board.clone(),
+                    tree.get(frame.node).and_then(|node| node.parent),
+                ) else {
+                    return Err(PgnError::UnexpectedToken {
+                        line,
+                        token: "(".to_string(),
+                    });
+                };
+                let variation = Frame {
+                    node: parent,
+                    ply: frame.ply - 1,
+                    board: prev_board,
+                    prev_board: None,
+                    has_moves: false,
+                    pending_comments: Vec::new(),
+                };
+                stack.push(std::mem::replace(&mut frame, variation));
+            }
+            PgnToken::VariationClose => {
+                frame = stack
+                    .pop()
+                    .ok_or(PgnError::UnbalancedVariation { line })?;
+            }
+            PgnToken::TagOpen => {
+                // next game started without a termination marker
+                break;
+            }
+            PgnToken::TagClose | PgnToken::Str(_) => {
+                return Err(PgnError::UnexpectedToken {
+                    line,
+                    token: format!("{:?}", token),
+                })
+            }
+        }
+    }
+    if !stack.is_empty() {
+        let line = tokens.last().map(|(_, line)| *line).unwrap_or(0);
+        return Err(PgnError::UnbalancedVariation { line });
+    }
+
+    if !has_result_tag {
+        if let Some(result) = game_termination {
+            metadata.result = result;
+        }
+    }
+    metadata.move_list = tree.mainline_moves();
+    metadata.move_tree = tree;
+    Ok(metadata)
+}
+
+/// Absolute half-move index of the first move after `fen`, so move numbers
+/// respect positions that start with Black to move or deep into a game.
+pub fn starting_ply(fen: &str) -> usize {
+    let parts: Vec<&str> = fen.split_whitespace().collect();
+    let fullmove = parts
+        .get(5)
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let black_to_move = parts.get(1) == Some(&"b");
+    (fullmove - 1) * 2 + black_to_move as usize
+}
+
+fn push_token(out: &mut String, token: &str) {
+    if !out.is_empty() && !out.ends_with('(') {
+        out.push(' ');
+    }
+    out.push_str(token);
+}
+
+/// Which optional parts of a game end up in exported movetext.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct PgnExportOptions {
+    // comments, NAGs and variations
+    pub include_annotations: bool,
+    // [%clk] and [%timestamp]
+    pub include_clocks: bool,
+    // [%eval]
+    pub include_evals: bool,
+}
+
+impl Default for PgnExportOptions {
+    fn default() -> Self {
+        Self {
+            include_annotations: true,
+            include_clocks: true,
+            include_evals: true,
+        }
+    }
+}
+
+/// Parse the value of an `[%eval]` command: pawns ("0.35", "-1.2") or mate ("#3", "#-2").
+pub fn parse_eval_command(value: &str) -> Option<EvalResponse> {
+    match value.strip_prefix('#') {
+        Some(mate) => Some(EvalResponse {
+            value: mate.parse::<f32>().ok()?,
+            kind: EvalType::Mate,
+        }),
+        None => Some(EvalResponse {
+            value: value.parse::<f32>().ok()? * 100.0,
+            kind: EvalType::Centipawn,
+        }),
+    }
+}
+
+pub fn format_eval_command(eval: &EvalResponse) -> String {
+    match eval.kind {
+        EvalType::Mate => format!("[%eval #{}]", eval.value as i32),
+        EvalType::Centipawn => format!("[%eval {:.2}]", eval.value / 100.0),
+    }
+}
+
+fn move_comment(mv: &MoveStruct, comments: &[String], options: &PgnExportOptions) -> String {
+    let mut comment_parts = String::new();
+    if options.include_clocks {
+        if let Some(clk) = mv.clock.as_ref() {
+            write!(comment_parts, "[%clk {}]", clk).unwrap();
+        }
+        if let Some(ts) = mv.time_stamp {
+            write!(comment_parts, "[%timestamp {}]", ts).unwrap();
+        }
+    }
+    if options.include_evals {
+        if let Some(eval) = mv.eval.as_ref() {
+            comment_parts.push_str(&format_eval_command(eval));
+        }
+    }
+    if options.include_annotations {
+        for comment in comments {
+            if !comment_parts.is_empty() {
+                comment_parts.push(' ');
+            }
+            comment_parts.push_str(comment);
+        }
+    }
+    comment_parts
+}
+
+/// Write one move. Returns true if a comment was written after it, in which
+/// case the next Black move needs its own move number.
+fn write_move(
+    tree: &MoveTree,
+    id: usize,
+    ply: usize,
+    force_number: bool,
+    options: &PgnExportOptions,
+    out: &mut String,
+) -> bool {
+    let Some(node) = tree.get(id) else {
+        return false;
+    };
+    let Some(mv) = node.mv.as_ref() else {
+        return false;
+    };
+    let pre_comments: &[String] = if options.include_annotations {
+        &node.pre_comments
+    } else {
+        &[]
+    };
+    for comment in pre_comments {
+        push_token(out, &format!("{{{}}}", comment));
+    }
+    let move_number = ply / 2 + 1;
+    if ply % 2 == 0 {
+        push_token(out, &format!("{}.", move_number));
+    } else if force_number || !pre_comments.is_empty() {
+        push_token(out, &format!("{}...", move_number));
+    }
+    push_token(out, &mv.san);
+    if options.include_annotations {
+        for nag in &node.nags {
+            push_token(out, &format!("${}", nag));
+        }
+    }
+    let comment = move_comment(mv, &node.comments, options);
+    if !comment.is_empty() {
+        push_token(out, &format!("{{{}}}", comment));
+        return true;
+    }
+    false
+}
+
+fn write_children(
+    tree: &MoveTree,
+    parent: usize,
+    ply: usize,
+    force_number: bool,
+    options: &PgnExportOptions,
+    out: &mut String,
+) {
+    let Some(node) = tree.get(parent) else {
+        return;
+    };
+    let Some((&main, variations)) = node.children.split_first() else {
+        return;
+    };
+    let mut needs_number = write_move(tree, main, ply, force_number, options, out);
+    if options.include_annotations {
+        for variation in variations {
+            push_token(out, "(");
+            let commented = write_move(tree, *variation, ply, true, options, out);
+            write_children(tree, *variation, ply + 1, commented, options, out);
+            out.push(')');
+            needs_number = true;
+        }
+    }
+    write_children(tree, main, ply + 1, needs_number, options, out);
+}
+
+/// Movetext for a game, without the termination marker.
+/// Games that only carry a flat `move_list` (e.g. from the game controller)
+/// are written from that list.
+pub fn write_movetext(metadata: &BoardMetaData, options: &PgnExportOptions) -> String {
+    let list_tree;
+    let tree = if metadata.move_tree.is_empty() {
+        list_tree = MoveTree::from_move_list(&metadata.starting_position, &metadata.move_list);
+        &list_tree
+    } else {
+        &metadata.move_tree
+    };
+
+    let mut out = String::new();
+    if options.include_annotations {
+        if let Some(root) = tree.get(ROOT_NODE) {
+            for comment in &root.comments {
+                push_token(&mut out, &format!("{{{}}}", comment));
+            }
+        }
+    }
+    write_children(
+        tree,
+        ROOT_NODE,
+        starting_ply(&metadata.starting_position),
+        true,
+        options,
+        &mut out,
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::board::EvalType;
+    use crate::engine::move_tree::ROOT_NODE;
+
+    fn sans(metadata: &BoardMetaData) -> Vec<String> {
+        metadata.move_list.iter().map(|mv| mv.san.clone()).collect()
+    }
+
+    #[test]
+    fn variations_hang_off_the_right_move() {
+        let metadata = parse_pgn("1. e4 e5 (1... c5 2. Nf3 (2. c3) d6) 2. Nf3 Nc6 *").unwrap();
+        assert_eq!(sans(&metadata), ["e4", "e5", "Nf3", "Nc6"]);
+
+        let tree = &metadata.move_tree;
+        let e4 = tree.get(ROOT_NODE).unwrap().children[0];
+        let replies = &tree.get(e4).unwrap().children;
+        assert_eq!(replies.len(), 2);
+        let c5 = tree.get(replies[1]).unwrap();
+        assert_eq!(c5.mv.as_ref().unwrap().san, "c5");
+        // 2. Nf3 with 2. c3 as its alternative
+        let nf3 = tree.get(c5.children[0]).unwrap();
+        assert_eq!(nf3.children.len(), 1);
+        let alternatives = &tree.get(nf3.parent.unwrap()).unwrap().children;
+        let c3 = tree.get(alternatives[1]).unwrap();
+        assert_eq!(c3.mv.as_ref().unwrap().san, "c3");
+    }
+
+    #[test]
+    fn unclosed_variation_is_an_error() {
+        assert!(parse_pgn("1. e4 e5 (1... c5 2. Nf3 *").is_err());
+    }
+
+    #[test]
+    fn fen_start() {
+        let pgn =
+            "[SetUp \"1\"]\n[FEN \"8/P7/8/8/8/8/8/k6K w - - 0 40\"]\n\n40. a8=Q+ Kb2 41. Qb7+ *";
+        let metadata = parse_pgn(pgn).unwrap();
+        assert_eq!(metadata.starting_position, "8/P7/8/8/8/8/8/k6K w - - 0 40");
+        assert_eq!(sans(&metadata), ["a8=Q+", "Kb2", "Qb7+"]);
+        assert_eq!(metadata.move_list[0].uci, "a7a8q");
+
+        // Black can't move twice
+        let pgn = "[FEN \"8/P7/8/8/8/8/8/k6K w - - 0 40\"]\n\n40. a8=Q+ Kb2 41... Kc2 *";
+        assert!(parse_pgn(pgn).is_err());
+    }
+
+    #[test]
+    fn nags_and_suffixes() {
+        let metadata = parse_pgn("1. e4 $1 e5?! 2. Nf3 $14 Nc6!! *").unwrap();
+        let tree = &metadata.move_tree;
+        let nags: Vec<Vec<u8>> = tree
+            .mainline()
+            .into_iter()
+            .map(|id| tree.get(id).unwrap().nags.clone())
+            .collect();
+        assert_eq!(nags, [vec![1], vec![6], vec![14], vec![3]]);
+        assert_eq!(sans(&metadata), ["e4", "e5", "Nf3", "Nc6"]);
+    }
+
+    #[test]
+    fn clock_and_eval_comments() {
+        let metadata = parse_pgn(
+            "1. e4 { [%eval 0.31] [%clk 0:03:00] good } e5 { [%eval #-2] [%clk 0:02:58.5] } *",
+        )
+        .unwrap();
+        let (e4, e5) = (&metadata.move_list[0], &metadata.move_list[1]);
+        assert_eq!(e4.clock.as_deref(), Some("0:03:00"));
+        assert_eq!(e5.clock.as_deref(), Some("0:02:58.5"));
+
+        let e4_eval = e4.eval.as_ref().unwrap();
+        assert!(matches!(e4_eval.kind, EvalType::Centipawn));
+        assert_eq!(e4_eval.value, 31.0);
+        let e5_eval = e5.eval.as_ref().unwrap();
+        assert!(matches!(e5_eval.kind, EvalType::Mate));
+        assert_eq!(e5_eval.value, -2.0);
+
+        // the commands are taken out of the comment text
+        let tree = &metadata.move_tree;
+        let first = tree.get(tree.mainline()[0]).unwrap();
+        assert_eq!(first.comments, ["good"]);
+    }
+
+    #[test]
+    fn unknown_date_and_ratings() {
+        let metadata = parse_pgn("[WhiteElo \"?\"]\n[BlackElo \"-\"]\n\n1. e4 *").unwrap();
+        assert_eq!(metadata.date, UNKNOWN_DATE);
+        assert_eq!(
+            (metadata.white_player_elo, metadata.black_player_elo),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn termination_tags() {
+        assert!(matches!(
+            parse_termination("Stalemate"),
+            TerminationReason::StaleMate
+        ));
+        assert!(matches!(
+            parse_termination("White won by checkmate"),
+            TerminationReason::Checkmate
+        ));
+        assert!(matches!(
+            parse_termination("Black won on time"),
+            TerminationReason::Timeout
+        ));
+        assert!(matches!(
+            parse_termination("abandoned"),
+            TerminationReason::Unknown
+        ));
+    }
+}
@@ -1,5 +1,160 @@
-use crate::engine::{Board, PieceType};
+use crate::engine::{move_gen::MoveError, Board, PieceColor, PieceType};
 
 impl Board {
     pub fn encode_san_move(&self, from: (u8, u8), to: (u8, u8), promotion: Option<PieceType>) {}
+
+    /// Resolve a SAN token (e.g. "Nbd7", "exd6", "e8=Q+", "O-O-O") against the
+    /// current position and return the (from, to, promotion) triple that
+    /// `move_piece` expects.
+    ///
+    /// Unlike `san_to_uci` this only accepts a token if exactly one legal move of
+    /// the side to move matches it, so ambiguous or illegal SAN is rejected.
+    pub fn resolve_san(
+        &mut self,
+        san: &str,
+    ) -> Result<((u8, u8), (u8, u8), Option<PieceType>), MoveError> {
+        self.rerender_move_cache();
+        let token = san
+            .trim()
+            .trim_end_matches(|c: char| matches!(c, '+' | '#' | '!' | '?'));
+
+        let back_rank: u8 = match self.turn {
+            PieceColor::White => 7,
+            PieceColor::Black => 0,
+        };
+        match token {
+            "O-O" | "0-0" => return self.resolve_castle((back_rank, 4), (back_rank, 6)),
+            "O-O-O" | "0-0-0" => return self.resolve_castle((back_rank, 4), (back_rank, 2)),
+            _ => {}
+        }
+
+        let mut chars: Vec<char> = token.chars().collect();
+
+        // Promotion: "e8=Q" or the older "e8Q"
+        let mut promotion: Option<PieceType> = None;
+        if let Some(eq_idx) = chars.iter().position(|c| *c == '=') {
+            promotion = match chars.get(eq_idx + 1) {
+                Some(c) => Some(Self::promotion_kind(*c).ok_or(MoveError::IllegalMove)?),
+                None => return Err(MoveError::IllegalMove),
+            };
+            chars.truncate(eq_idx);
+        } else if chars.len() >= 3
+            && chars[chars.len() - 2].is_ascii_digit()
+            && "QRBN".contains(chars[chars.len() - 1])
+            && chars[0].is_ascii_lowercase()
+        {
+            promotion = Self::promotion_kind(chars[chars.len() - 1]);
+            chars.pop();
+        }
+
+        let kind = match chars.first() {
+            Some('K') => PieceType::King,
+            Some('Q') => PieceType::Queen,
+            Some('R') => PieceType::Rook,
+            Some('B') => PieceType::Bishop,
+            Some('N') => PieceType::Knight,
+            Some(c) if ('a'..='h').contains(c) => PieceType::Pawn,
+            _ => return Err(MoveError::IllegalMove),
+        };
+        if kind != PieceType::Pawn {
+            chars.remove(0);
+        }
+        // Capture markers carry no information we need to match on
+        chars.retain(|c| !matches!(c, 'x' | ':' | '-'));
+        if chars.len() < 2 {
+            return Err(MoveError::IllegalMove);
+        }
+
+        let dest = Self::square_from_chars(chars[chars.len() - 2], chars[chars.len() - 1])
+            .ok_or(MoveError::IllegalMove)?;
+
+        // Whatever is left in front of the destination is disambiguation
+        let mut from_file: Option<u8> = None;
+        let mut from_rank: Option<u8> = None;
+        for c in &chars[..chars.len() - 2] {
+            match c {
+                'a'..='h' => from_file = Some(*c as u8 - b'a'),
+                '1'..='8' => from_rank = Some(8 - (*c as u8 - b'0')),
+                _ => return Err(MoveError::IllegalMove),
+            }
+        }
+
+        let mut candidates: Vec<(u8, u8)> = Vec::new();
+        for i in 0..8u8 {
+            for j in 0..8u8 {
+                let Some(piece) = &self.squares[i as usize][j as usize] else {
+                    continue;
+                };
+                if piece.kind != kind || piece.color != self.turn {
+                    continue;
+                }
+                if from_file.is_some_and(|f| f != j) || from_rank.is_some_and(|r| r != i) {
+                    continue;
+                }
+                if let Some(pms) = self.move_cache.get(&piece.id) {
+                    if pms.quiet_moves.contains(&dest) || pms.capture_moves.contains(&dest) {
+                        candidates.push((i, j));
+                    }
+                }
+            }
+        }
+
+        match candidates.as_slice() {
+            [from] => {
+                let reaches_last_rank = kind == PieceType::Pawn && (dest.0 == 0 || dest.0 == 7);
+                if reaches_last_rank && promotion.is_none() {
+                    promotion = Some(PieceType::Queen);
+                }
+                if !reaches_last_rank {
+                    promotion = None;
+                }
+                Ok((*from, dest, promotion))
+            }
+            _ => Err(MoveError::IllegalMove),
+        }
+    }
+
+    /// "+" or "#" for the side to move, to append to the SAN of the move just played.
+    pub fn san_check_suffix(&mut self) -> &'static str {
+        if self.is_checkmate() {
+            "#"
+        } else if self.is_in_check(self.turn) {
+            "+"
+        } else {
+            ""
+        }
+    }
+
+    fn resolve_castle(
+        &self,
+        from: (u8, u8),
+        to: (u8, u8),
+    ) -> Result<((u8, u8), (u8, u8), Option<PieceType>), MoveError> {
+        match &self.squares[from.0 as usize][from.1 as usize] {
+            Some(king) if king.kind == PieceType::King && king.color == self.turn => {
+                match self.move_cache.get(&king.id) {
+                    Some(pms) if pms.quiet_moves.contains(&to) => Ok((from, to, None)),
+                    _ => Err(MoveError::IllegalMove),
+                }
+            }
+            _ => Err(MoveError::IllegalMove),
+        }
+    }
+
+    fn promotion_kind(c: char) -> Option<PieceType> {
+        match c.to_ascii_uppercase() {
+            'Q' => Some(PieceType::Queen),
+            'R' => Some(PieceType::Rook),
+            'B' => Some(PieceType::Bishop),
+            'N' => Some(PieceType::Knight),
+            _ => None,
+        }
+    }
+
+    fn square_from_chars(file: char, rank: char) -> Option<(u8, u8)> {
+        if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+            return None;
+        }
+        Some((8 - (rank as u8 - b'0'), file as u8 - b'a'))
+    }
 }
@@ -5,6 +5,7 @@ use ts_rs::TS;
 
 use crate::analyzer::analyzer::{AnalyzerController, UndoInfo};
 use crate::engine::board::{GamePhase, GameResult, TerminationBy};
+use crate::engine::move_tree::MoveTree;
 use crate::engine::{Board, PieceColor};
 use crate::game::controller::TerminationReason;
 
@@ -47,6 +48,8 @@ pub struct SerializedAnalyzerController {
     pub game_id: usize,
     pub serialized_board: SerializedBoard,
     pub current_ply: i32,
+    pub current_node: usize,
+    pub move_tree: MoveTree,
     pub board_undo: Vec<UndoInfo>,
     //pub chat_history: AiChatStructure,
 }
@@ -135,6 +138,8 @@ pub fn serialize_analyzer_controller(
         game_id: controller.game_id,
         serialized_board: serialize_board(&controller.board),
         current_ply: controller.current_ply as i32,
+        current_node: controller.current_node,
+        move_tree: controller.board.meta_data.move_tree.clone(),
         board_undo: controller.board_undo.clone(),
         //chat_history: controller.chat_history.clone(),
     }
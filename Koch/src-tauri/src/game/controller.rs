@@ -4,7 +4,6 @@ use std::{
     sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
-use stockfish::Stockfish;
 use ts_rs::TS;
 
 use crate::{
@@ -14,10 +13,15 @@ use crate::{
         serializer::{serialize_board, SerializedBoard},
         Board, PieceColor, PieceType,
     },
+    game::rating::{link_rating_to_game, rating_change},
     make_engine_move,
     server::server::ServerState,
 };
 
+// search of the engine's moves, the depth the stockfish crate's `go` used;
+// its strength comes from UCI_Elo
+pub const ENGINE_MOVE_GO_COMMAND: &str = "go depth 15";
+
 #[derive(Clone, Debug)]
 
 pub struct ChessClock {
@@ -27,6 +31,52 @@ pub struct ChessClock {
     pub is_active: bool,
     pub active_color: PieceColor,
 }
+impl GameControllerMode {
+    /// Classify a PGN TimeControl ("300", "180+2", "1/86400") by estimated game
+    /// length (base + 40 * increment), the same way the online sites do.
+    pub fn from_time_control(time_control: &str) -> Option<Self> {
+        let first = time_control.split(':').next()?.trim();
+        if first.contains('/') {
+            // moves/seconds periods are correspondence or old classical controls
+            return Some(GameControllerMode::Classical);
+        }
+        let (base, increment) = match first.split_once('+') {
+            Some((base, inc)) => (base.parse::<f32>().ok()?, inc.parse::<f32>().ok()?),
+            None => (first.parse::<f32>().ok()?, 0.0),
+        };
+        let estimated = base + 40.0 * increment;
+        Some(if estimated < 180.0 {
+            GameControllerMode::Bullet
+        } else if estimated < 480.0 {
+            GameControllerMode::Blitz
+        } else if estimated < 1500.0 {
+            GameControllerMode::Rapid
+        } else {
+            GameControllerMode::Classical
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameControllerMode::Bullet => "bullet",
+            GameControllerMode::Blitz => "blitz",
+            GameControllerMode::Rapid => "rapid",
+            GameControllerMode::Classical => "classical",
+        }
+    }
+
+    /// Inverse of `as_str`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bullet" => Some(GameControllerMode::Bullet),
+            "blitz" => Some(GameControllerMode::Blitz),
+            "rapid" => Some(GameControllerMode::Rapid),
+            "classical" => Some(GameControllerMode::Classical),
+            _ => None,
+        }
+    }
+}
+
 impl From<GameControllerMode> for ChessClock {
     fn from(mode: GameControllerMode) -> Self {
         let duration = match mode {
@@ -74,6 +124,8 @@ pub enum TerminationReason {
     Draw,
     Timeout,
     Resignation,
+    // not recorded, or not a reason listed here
+    Unknown,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, TS)]
@@ -100,6 +152,10 @@ pub struct GameController {
     pub result: Option<GameResult>,
     pub elo_gain: Option<i32>,
     pub can_be_abandoned: bool,
+    // rated games the player had in this time class when the game started
+    pub rated_games: u32,
+    // `rating_history` row of this game, once its rating change is recorded
+    pub rating_entry: Option<i64>,
 }
 #[derive(Clone, Debug, Serialize, Deserialize, TS)]
 #[ts(export)]
@@ -140,6 +196,8 @@ impl From<GameControllerMode> for GameController {
             result: None,
             elo_gain: None,
             can_be_abandoned: true,
+            rated_games: 0,
+            rating_entry: None,
         }
     }
 }
@@ -175,14 +233,52 @@ impl GameController {
             GameControllerMode::Rapid => Some("600".to_string()),
             GameControllerMode::Classical => Some("1800".to_string()),
         };
+        self.board.meta_data.white_player_elo = self.white_elo as u32;
         self.board.meta_data.black_player_elo = self.black_elo as u32;
-        self.board.meta_data.black_player_elo = self.white_elo as u32;
         self.board.meta_data.black_player_name = self.black_name.clone();
         self.board.meta_data.white_player_name = self.white_name.clone();
         self.board.rerender_move_cache();
         self.can_be_abandoned = true;
+        self.elo_gain = None;
+        self.rating_entry = None;
         self.serialize()
     }
+    /// Seat the player's and the engine's ratings on the colors they play.
+    pub fn set_ratings(&mut self, player_rating: u32, engine_rating: u32, rated_games: u32) {
+        let (white, black) = match self.player {
+            PieceColor::White => (player_rating, engine_rating),
+            PieceColor::Black => (engine_rating, player_rating),
+        };
+        self.white_elo = white as usize;
+        self.black_elo = black as usize;
+        self.board.meta_data.white_player_elo = white;
+        self.board.meta_data.black_player_elo = black;
+        self.rated_games = rated_games;
+    }
+    pub fn player_rating(&self) -> (u32, u32) {
+        match self.player {
+            PieceColor::White => (self.white_elo as u32, self.black_elo as u32),
+            PieceColor::Black => (self.black_elo as u32, self.white_elo as u32),
+        }
+    }
+    /// Player's score in the finished game: 1 win, 0.5 draw, 0 loss.
+    pub fn player_score(&self) -> Option<f64> {
+        match (&self.result, self.player) {
+            (Some(GameResult::Draw), _) => Some(0.5),
+            (Some(GameResult::WhiteWin), PieceColor::White)
+            | (Some(GameResult::BlackWin), PieceColor::Black) => Some(1.0),
+            (Some(GameResult::WhiteWin), PieceColor::Black)
+            | (Some(GameResult::BlackWin), PieceColor::White) => Some(0.0),
+            _ => None,
+        }
+    }
+    // Elo change of the finished game, see `game::rating`
+    fn settle_rating(&mut self) {
+        let (player, engine) = self.player_rating();
+        self.elo_gain = self
+            .player_score()
+            .map(|score| rating_change(player, engine, score, self.rated_games));
+    }
     pub fn export() {}
     pub fn end_game(
         &mut self,
@@ -199,21 +295,11 @@ impl GameController {
                 PieceColor::Black => Some(GameResult::WhiteWin),
             },
             TerminationReason::StaleMate | TerminationReason::Draw => Some(GameResult::Draw),
+            TerminationReason::Unknown => None,
         };
+        // games abandoned before both sides moved are not rated
         if !(self.board.meta_data.move_list.len() < 2) {
-            // Compute elo_gain using loser variable
-            self.elo_gain = match reason {
-                TerminationReason::Checkmate
-                | TerminationReason::Timeout
-                | TerminationReason::Resignation => {
-                    if self.player != loser {
-                        Some(10)
-                    } else {
-                        Some(-10)
-                    }
-                }
-                TerminationReason::StaleMate | TerminationReason::Draw => Some(0),
-            };
+            self.settle_rating();
         }
         self.serialize()
     }
@@ -294,30 +380,9 @@ impl GameController {
                             Some(GameResult::WhiteWin)
                         }
                     }
-                    None => None,
-                };
-                // Compute elo_gain in a single line
-                self.elo_gain = match self.result {
-                    Some(GameResult::WhiteWin) if self.player == PieceColor::White => Some(10),
-                    Some(GameResult::BlackWin) if self.player == PieceColor::Black => Some(10),
-                    Some(GameResult::WhiteWin) | Some(GameResult::BlackWin) => Some(-10),
-                    Some(GameResult::Draw) => {
-                        let delta = (self.white_elo as i32 - self.black_elo as i32).abs();
-                        if delta > 10 {
-                            if (self.player == PieceColor::White && self.white_elo > self.black_elo)
-                                || (self.player == PieceColor::Black
-                                    && self.black_elo > self.white_elo)
-                            {
-                                Some(-1)
-                            } else {
-                                Some(1)
-                            }
-                        } else {
-                            Some(0)
-                        }
-                    }
-                    _ => Some(0),
+                    Some(TerminationReason::Unknown) | None => None,
                 };
+                self.settle_rating();
             }
         }
         self.board.rerender_move_cache();
@@ -370,6 +435,7 @@ pub fn change_gamemode(
 ) -> SerializedGameController {
     let mut state = state.lock().unwrap();
     state.game_controller.change_mode(new_mode);
+    state.apply_ratings();
     return state.game_controller.serialize();
 }
 #[tauri::command]
@@ -378,7 +444,10 @@ pub fn start_game(state: tauri::State<'_, Mutex<ServerState>>) -> SerializedGame
     if let Some(engine) = &mut state.engine {
         engine.setup_for_new_game().ok();
     };
-    return state.game_controller.start();
+    state.game_controller.start();
+    // the player's color was just drawn, seat the ratings again
+    state.apply_ratings();
+    return state.game_controller.serialize();
 }
 #[tauri::command]
 pub fn update_game_state(
@@ -397,10 +466,9 @@ pub fn update_game_state(
                         return Err(e.to_string());
                     }
                     engine.ensure_ready().ok();
-                    match engine.go() {
-                        Ok(out) => {
-                            let best_move = out.best_move();
-                            if let Some((from, to, promotion)) = board.decode_uci_move(best_move) {
+                    match engine.best_move(ENGINE_MOVE_GO_COMMAND) {
+                        Ok(best_move) => {
+                            if let Some((from, to, promotion)) = board.decode_uci_move(&best_move) {
                                 (from, to, promotion)
                             } else {
                                 return Err("failed uci ".into());
@@ -448,7 +516,7 @@ pub fn update_game_state(
             .clone()
     );
     if serialized.elo_gain.is_some() {
-        state_guard.update_elo(serialized.elo_gain.unwrap());
+        state_guard.record_game_rating();
     }
 
     Ok(serialized)
@@ -463,7 +531,7 @@ pub fn end_game(
     let mut state = state.lock().unwrap();
     let serialized = state.game_controller.end_game(reason, loser);
     if serialized.elo_gain.is_some() {
-        state.update_elo(serialized.elo_gain.unwrap());
+        state.record_game_rating();
     }
     serialized
 }
@@ -471,39 +539,8 @@ pub fn end_game(
 pub fn new_game(state: tauri::State<'_, Mutex<ServerState>>) -> SerializedGameController {
     let mut state = state.lock().unwrap();
 
-    let default_player_elo = "500".to_string();
-    let default_engine_elo = "500".to_string();
-    let (player_elo, engine_elo) = {
-        (
-            state
-                .settings
-                .map
-                .get("PlayerElo")
-                .unwrap_or(&default_player_elo)
-                .clone(),
-            state
-                .settings
-                .map
-                .get("StockfishElo")
-                .unwrap_or(&default_engine_elo)
-                .clone(),
-        )
-    };
     state.game_controller = GameController::new();
-    match state.game_controller.player {
-        PieceColor::White => {
-            state.game_controller.white_elo = player_elo.parse().unwrap_or(500);
-            state.game_controller.black_elo = engine_elo.parse().unwrap_or(500);
-        }
-        PieceColor::Black => {
-            state.game_controller.white_elo = engine_elo.parse().unwrap_or(500);
-            state.game_controller.black_elo = player_elo.parse().unwrap_or(500);
-        }
-    };
-    if let Some(ref mut engine) = &mut state.engine {
-        engine.set_elo(engine_elo.parse().unwrap_or(500)).unwrap();
-        engine.ensure_ready().ok();
-    }
+    state.apply_ratings();
     state.game_controller.serialize()
 }
 #[tauri::command]
@@ -540,8 +577,15 @@ pub fn save_appgame(state: tauri::State<'_, Mutex<ServerState>>) -> Result<(), S
         end_time: state.game_controller.board.meta_data.end_time.clone(),
         link: state.game_controller.board.meta_data.link.clone(),
         eco: state.game_controller.board.meta_data.eco.clone(),
+        extra_tags: state.game_controller.board.meta_data.extra_tags.clone(),
+        move_tree: state.game_controller.board.meta_data.move_tree.clone(),
+        game_id: None,
     };
-    save_game(&metadata).map_err(|e| e.to_string())?;
+    let mut con = state.database.connection().map_err(|e| e.to_string())?;
+    let outcome = save_game(&mut con, &metadata).map_err(|e| e.to_string())?;
+    if let Some(entry_id) = state.game_controller.rating_entry {
+        link_rating_to_game(&con, entry_id, outcome.game_id()).map_err(|e| e.to_string())?;
+    }
     Ok(())
 }
 #[tauri::command]
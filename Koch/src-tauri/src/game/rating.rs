@@ -0,0 +1,192 @@
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::database::db::state_connection;
+use crate::game::controller::GameControllerMode;
+use crate::server::server::ServerState;
+
+// rating of a class without rated games
+pub const DEFAULT_RATING: u32 = 500;
+// settings key of the single rating older versions kept for every class
+pub const LEGACY_RATING_SETTING: &str = "PlayerElo";
+// the engine is set this much above the player's rating
+pub const ENGINE_RATING_OFFSET: u32 = 50;
+const LOWEST_RATING: i32 = 100;
+// FIDE-style K: larger while provisional, smaller once strong
+const PROVISIONAL_GAMES: u32 = 30;
+
+/// Probability-like expected score of `rating` against `opponent`, 0.0 - 1.0.
+pub fn expected_score(rating: u32, opponent: u32) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent as f64 - rating as f64) / 400.0))
+}
+
+pub fn k_factor(rating: u32, rated_games: u32) -> f64 {
+    if rated_games < PROVISIONAL_GAMES {
+        40.0
+    } else if rating < 2400 {
+        20.0
+    } else {
+        10.0
+    }
+}
+
+/// Elo change after scoring `score` (1 win, 0.5 draw, 0 loss) against `opponent`.
+pub fn rating_change(rating: u32, opponent: u32, score: f64, rated_games: u32) -> i32 {
+    let change = k_factor(rating, rated_games) * (score - expected_score(rating, opponent));
+    (change.round() as i32).max(LOWEST_RATING - rating as i32)
+}
+
+/// One rated game, for charting the rating of a time class.
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct RatingEntry {
+    pub entry_id: u32,
+    pub time_class: GameControllerMode,
+    // set once the game is saved
+    pub game_id: Option<u32>,
+    pub rating_before: u32,
+    pub rating_after: u32,
+    pub opponent_rating: u32,
+    pub score: f32,
+    // empty for the rating carried over from the old `PlayerElo` setting
+    pub recorded_at: String,
+}
+
+#[derive(Clone, Debug, TS, Serialize, Deserialize)]
+#[ts(export)]
+pub struct ClassRating {
+    pub time_class: GameControllerMode,
+    pub rating: u32,
+    pub rated_games: u32,
+}
+
+/// Current rating and number of rated games in `time_class`, None before the first one.
+pub fn current_rating(
+    con: &Connection,
+    time_class: GameControllerMode,
+) -> rusqlite::Result<Option<(u32, u32)>> {
+    // a carried over rating has no `recorded_at` and isn't a game
+    con.query_row(
+        "SELECT rating_after,
+            (SELECT COUNT(recorded_at) FROM rating_history WHERE time_class = ?1)
+         FROM rating_history WHERE time_class = ?1
+         ORDER BY entry_id DESC LIMIT 1",
+        [time_class.as_str()],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+pub fn record_rating(
+    con: &Connection,
+    time_class: GameControllerMode,
+    rating_before: u32,
+    rating_after: u32,
+    opponent_rating: u32,
+    score: f64,
+) -> rusqlite::Result<i64> {
+    con.execute(
+        "INSERT INTO rating_history
+            (time_class, rating_before, rating_after, opponent_rating, score, recorded_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))",
+        params![
+            time_class.as_str(),
+            rating_before,
+            rating_after,
+            opponent_rating,
+            score
+        ],
+    )?;
+    Ok(con.last_insert_rowid())
+}
+
+/// Start `time_class` at `rating`, the flat rating of older versions.
+pub fn seed_rating(
+    con: &Connection,
+    time_class: GameControllerMode,
+    rating: u32,
+) -> rusqlite::Result<()> {
+    con.execute(
+        "INSERT INTO rating_history
+            (time_class, rating_before, rating_after, opponent_rating, score)
+         VALUES (?1, ?2, ?2, ?2, 0.5)",
+        params![time_class.as_str(), rating],
+    )?;
+    Ok(())
+}
+
+pub fn link_rating_to_game(con: &Connection, entry_id: i64, game_id: u32) -> rusqlite::Result<()> {
+    con.execute(
+        "UPDATE rating_history SET game_id = ?1 WHERE entry_id = ?2",
+        params![game_id, entry_id],
+    )?;
+    Ok(())
+}
+
+pub fn rating_history(
+    con: &Connection,
+    time_class: Option<GameControllerMode>,
+) -> rusqlite::Result<Vec<RatingEntry>> {
+    let mut stmt = con.prepare(
+        "SELECT entry_id, time_class, game_id, rating_before, rating_after, opponent_rating,
+            score, recorded_at
+         FROM rating_history
+         WHERE ?1 IS NULL OR time_class = ?1
+         ORDER BY entry_id",
+    )?;
+    let rows = stmt.query_map([time_class.map(|c| c.as_str())], |row| {
+        let time_class: String = row.get(1)?;
+        Ok(RatingEntry {
+            entry_id: row.get(0)?,
+            time_class: GameControllerMode::from_name(&time_class)
+                .unwrap_or(GameControllerMode::Rapid),
+            game_id: row.get(2)?,
+            rating_before: row.get(3)?,
+            rating_after: row.get(4)?,
+            opponent_rating: row.get(5)?,
+            score: row.get::<_, f64>(6)? as f32,
+            recorded_at: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
+        })
+    })?;
+    rows.collect()
+}
+
+/// Rating changes of games against the engine, oldest first. `None` returns
+/// every time class.
+#[tauri::command]
+pub fn get_rating_history(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    time_class: Option<GameControllerMode>,
+) -> Result<Vec<RatingEntry>, String> {
+    let con = state_connection(&state)?;
+    rating_history(&con, time_class).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_ratings(
+    state: tauri::State<'_, Mutex<ServerState>>,
+) -> Result<Vec<ClassRating>, String> {
+    let con = state_connection(&state)?;
+    let classes = [
+        GameControllerMode::Bullet,
+        GameControllerMode::Blitz,
+        GameControllerMode::Rapid,
+        GameControllerMode::Classical,
+    ];
+    classes
+        .into_iter()
+        .map(|time_class| {
+            let (rating, rated_games) = current_rating(&con, time_class)
+                .map_err(|e| e.to_string())?
+                .unwrap_or((DEFAULT_RATING, 0));
+            Ok(ClassRating {
+                time_class,
+                rating,
+                rated_games,
+            })
+        })
+        .collect()
+}
@@ -20,21 +20,50 @@ use crate::analyzer::analyzer::get_threat;
 use crate::analyzer::analyzer::LocalChat;
 
 use crate::analyzer::analyzer::{
-    get_analyzer_settings, set_analyzer_fen, set_engine_option, start_analyzer_thread,
-    stop_analyzer,
+    get_analyzer_settings, set_analyzer_fen, set_analyzer_limit, set_engine_option,
+    start_analyzer_thread, stop_analyzer,
 };
 use crate::analyzer::analyzer::{AnalyzerController, EngineCommand};
 use crate::analyzer::board_interactions::{get_board_at_index, get_fen};
-use crate::database::create::create_database;
+use crate::analyzer::critical::get_critical_moments;
+use crate::analyzer::eval_series::get_eval_series;
+use crate::analyzer::game_analysis::{analyze_game, cancel_game_analysis, get_game_analysis};
+use crate::analyzer::variations::{
+    annotate_analyzer_node, delete_variation, demote_variation, goto_analyzer_node,
+    goto_sibling_variation, play_analyzer_move, promote_variation, save_analyzer_game,
+};
+use crate::database::collections::{
+    add_game_tag, add_games_to_collection, create_collection, delete_collection, get_all_tags,
+    get_collections, get_game_tags, remove_game_tag, remove_games_from_collection, set_favorite,
+    update_collection,
+};
 use crate::database::create::get_game_by_id;
 use crate::database::create::get_game_chat_by_id;
 use crate::database::create::get_game_list;
 use crate::database::create::load_pgn_game;
+use crate::database::db::{
+    get_database_error, get_database_path, set_database_path, state_connection,
+};
+use crate::database::edit::{
+    delete_game, delete_games, undo_game_change, update_game, update_game_tags,
+};
+use crate::database::engines::{
+    get_engines, register_engine, remove_engine, select_engine, set_uci_option,
+};
+use crate::database::export::export_games;
+use crate::database::import::{cancel_import, import_pgn_file};
 use crate::database::integrations::sync_with_chessdotcom;
+use crate::database::lichess::sync_with_lichess;
+use crate::database::stats::get_player_stats;
+use crate::game::rating::{get_rating_history, get_ratings};
+use crate::database::positions::search_games_by_fen;
+use crate::database::query::query_games;
 use crate::engine::board::{BoardMetaData, EvalResponse, GameResult};
+use crate::engine::move_tree::ROOT_NODE;
 use crate::engine::serializer::serialize_board;
 use crate::engine::serializer::SerializedBoard;
 use crate::game::controller::save_appgame;
+use crate::game::controller::ENGINE_MOVE_GO_COMMAND;
 use crate::game::controller::update_game_state;
 use crate::game::controller::GameController;
 use crate::game::controller::SerializedGameController;
@@ -51,8 +80,6 @@ use crate::{
 };
 use std::collections::BTreeMap;
 use std::time::Duration;
-use stockfish::EngineOutput;
-use stockfish::Stockfish;
 use tauri::{AppHandle, Builder, Emitter, Manager, Window};
 
 fn make_engine_move(state: &mut ServerState, fen: String) -> Option<String> {
@@ -63,14 +90,10 @@ fn make_engine_move(state: &mut ServerState, fen: String) -> Option<String> {
                 .set_fen_position(&fen)
                 .inspect_err(|e| eprintln!("{e}"))
                 .ok();
-            let engine_output = engine.go().inspect_err(|e| eprintln!("{e}")).ok();
-            match engine_output {
-                Some(engine_output) => {
-                    let uci_move = engine_output.best_move();
-                    return Some(uci_move.into());
-                }
-                None => None,
-            }
+            engine
+                .best_move(ENGINE_MOVE_GO_COMMAND)
+                .inspect_err(|e| eprintln!("{e}"))
+                .ok()
         }
         None => None,
     }
@@ -94,8 +117,10 @@ fn board_fen(board: Board) -> String {
 }
 
 #[tauri::command]
-fn fetch_game_history() -> Vec<BoardMetaData> {
-    match get_game_list() {
+fn fetch_game_history(state: tauri::State<'_, Mutex<ServerState>>) -> Vec<BoardMetaData> {
+    let games =
+        state_connection(&state).and_then(|con| get_game_list(&con).map_err(|e| e.to_string()));
+    match games {
         Ok(list) => list,
         Err(e) => {
             eprintln!("fetch_game_history DB error: {e}");
@@ -112,12 +137,26 @@ fn fetch_default_game(state: tauri::State<'_, Mutex<ServerState>>) -> AnalyzerCo
     analyzer
 }
 #[tauri::command]
-fn fetch_game(state: tauri::State<'_, Mutex<ServerState>>, id: usize) -> AnalyzerController {
+fn fetch_game(
+    state: tauri::State<'_, Mutex<ServerState>>,
+    id: usize,
+    ply: Option<usize>,
+) -> AnalyzerController {
     let mut state = state.lock().unwrap();
     if state.analyzer_controller.game_id == id {
+        if let Some(ply) = ply {
+            open_at_ply(&mut state.analyzer_controller, ply);
+        }
         return state.analyzer_controller.clone();
     }
-    let game_chat = match get_game_chat_by_id(id) {
+    let con = match state.database.connection() {
+        Ok(con) => con,
+        Err(e) => {
+            eprintln!("fetch_game: DB error: {e}");
+            return AnalyzerController::default();
+        }
+    };
+    let game_chat = match get_game_chat_by_id(&con, id) {
         Ok(chat) => chat,
         Err(e) => {
             eprintln!("fetch_game: get_game_chat_by_id DB error: {e}");
@@ -125,7 +164,7 @@ fn fetch_game(state: tauri::State<'_, Mutex<ServerState>>, id: usize) -> Analyze
         }
     };
 
-    match get_game_by_id(id) {
+    match get_game_by_id(&con, id) {
         Ok(list) => {
             let mut analyzer = AnalyzerController::default();
             let move_count = list.move_list.len();
@@ -135,6 +174,9 @@ fn fetch_game(state: tauri::State<'_, Mutex<ServerState>>, id: usize) -> Analyze
             analyzer.game_id = id;
             analyzer.current_ply = -1;
             analyzer.chat_history = game_chat;
+            if let Some(ply) = ply {
+                open_at_ply(&mut analyzer, ply);
+            }
 
             // Persist so do_move/undo_move operate on the same instance
             state.analyzer_controller = analyzer.clone();
@@ -147,6 +189,24 @@ fn fetch_game(state: tauri::State<'_, Mutex<ServerState>>, id: usize) -> Analyze
         }
     }
 }
+// `ply` counts half-moves played, as returned by `search_games_by_fen`
+fn open_at_ply(analyzer: &mut AnalyzerController, ply: usize) {
+    let target = match ply {
+        0 => Some(ROOT_NODE),
+        _ => analyzer
+            .board
+            .meta_data
+            .move_tree
+            .mainline()
+            .get(ply - 1)
+            .copied(),
+    };
+    if let Some(target) = target {
+        if let Err(e) = analyzer.goto_node(target) {
+            eprintln!("fetch_game: could not open at ply {ply}: {:?}", e);
+        }
+    }
+}
 #[tauri::command]
 fn get_settings(state: tauri::State<'_, Mutex<ServerState>>) -> Settings {
     let mut state = state.lock().unwrap();
@@ -164,9 +224,6 @@ pub fn run() {
         // Initialize state with default (None channels)
         .manage(Mutex::new(ServerState::default()))
         .setup(|app| {
-            println!("[DEBUG] Creating database...");
-
-            create_database();
             println!("[DEBUG] Initializing system info...");
             let mut sys = System::new_all();
             sys.refresh_all();
@@ -212,13 +269,60 @@ pub fn run() {
             stop_analyzer,
             board_fen,
             get_board_at_index,
+            play_analyzer_move,
+            goto_analyzer_node,
+            goto_sibling_variation,
+            promote_variation,
+            demote_variation,
+            delete_variation,
+            annotate_analyzer_node,
+            save_analyzer_game,
             get_system_information,
             set_engine_option,
             get_analyzer_settings,
+            set_analyzer_limit,
+            get_engines,
+            register_engine,
+            remove_engine,
+            set_uci_option,
+            select_engine,
+            analyze_game,
+            cancel_game_analysis,
+            get_game_analysis,
+            get_eval_series,
+            get_critical_moments,
             load_pgn_game,
+            import_pgn_file,
+            cancel_import,
+            export_games,
+            search_games_by_fen,
+            query_games,
+            update_game,
+            update_game_tags,
+            delete_game,
+            delete_games,
+            undo_game_change,
+            get_collections,
+            create_collection,
+            update_collection,
+            delete_collection,
+            add_games_to_collection,
+            remove_games_from_collection,
+            get_all_tags,
+            get_game_tags,
+            add_game_tag,
+            remove_game_tag,
+            set_favorite,
+            get_database_path,
+            get_database_error,
+            set_database_path,
             get_settings,
             update_settings,
             sync_with_chessdotcom,
+            sync_with_lichess,
+            get_player_stats,
+            get_rating_history,
+            get_ratings,
             get_threat,
             send_llm_request,
             end_game,
@@ -1,6 +1,15 @@
 use crate::analyzer::analyzer::{AnalyzerController, EngineCommand};
+use crate::analyzer::uci_engine::{UciEngine, UciInfo};
+use crate::database::db::Database;
+use crate::database::edit::GameUndo;
+use crate::database::engines::{engine_for, EngineConfig, EngineRole};
+use crate::game::rating::{
+    current_rating, record_rating, seed_rating, DEFAULT_RATING, ENGINE_RATING_OFFSET,
+    LEGACY_RATING_SETTING,
+};
 use crate::update_settings;
-use crate::{database, engine::Board, game::controller::GameController};
+use crate::engine::PieceColor;
+use crate::{engine::Board, game::controller::GameController};
 
 use serde::{Deserialize, Serialize};
 use sysinfo::System;
@@ -14,8 +23,8 @@ use std::io::{self, BufRead, BufReader, Read};
 use std::path::Path;
 use std::sync::mpsc::{self, SyncSender};
 use std::sync::mpsc::{Receiver, Sender};
-use std::sync::Mutex;
-use stockfish::{EngineEval, Stockfish};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
 use ts_rs::TS;
 
 #[derive(Clone, Debug, TS, Serialize, Deserialize)]
@@ -42,13 +51,82 @@ impl std::fmt::Display for PvLineData {
     }
 }
 
-#[derive(Clone, Debug, serde::Serialize, ts_rs::TS)]
+/// One move of a PV line, with the position it leads to.
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PvMove {
+    pub uci: String,
+    pub san: String,
+    // full move number and side of the move
+    pub move_number: u32,
+    pub color: PieceColor,
+    // position after the move
+    pub fen: String,
+}
+
+/// A PV line in SAN, e.g. "12...Nc6 13. Bb5 a6".
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SanLine {
+    pub text: String,
+    pub moves: Vec<PvMove>,
+}
+
+impl SanLine {
+    /// Play the UCI `moves` on a copy of `board`, which must have its move
+    /// cache rendered. Stops at the first move that isn't legal there.
+    pub fn from_uci(board: &Board, moves: &str) -> Self {
+        let mut board = board.clone();
+        let mut line = SanLine::default();
+        for uci in moves.split_whitespace() {
+            let Some((from, to, promotion)) = board.decode_uci_move(uci) else {
+                break;
+            };
+            let (move_number, color) = (board.fullmove_number, board.turn);
+            let Ok(mut mv) = board.move_piece(from, to, promotion) else {
+                break;
+            };
+            mv.san.push_str(board.san_check_suffix());
+            board.rerender_move_cache();
+
+            if !line.text.is_empty() {
+                line.text.push(' ');
+            }
+            match color {
+                PieceColor::White => line.text.push_str(&format!("{}. {}", move_number, mv.san)),
+                PieceColor::Black if line.moves.is_empty() => {
+                    line.text.push_str(&format!("{}...{}", move_number, mv.san))
+                }
+                PieceColor::Black => line.text.push_str(&mv.san),
+            }
+            line.moves.push(PvMove {
+                uci: uci.to_string(),
+                san: mv.san,
+                move_number,
+                color,
+                fen: board.to_string(),
+            });
+        }
+        line
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, ts_rs::TS)]
 #[ts(export)]
 pub struct PvObject {
     pub fen: String,
     pub depth: u32,
     // Changed: lines now map to PvLineData instead of just String
     pub lines: HashMap<u8, PvLineData>,
+    // same keys as `lines`; empty when the position isn't known as a FEN
+    #[serde(default)]
+    pub san_lines: HashMap<u8, SanLine>,
+    // full `info` of each line: nodes, speed, WDL, bounds
+    #[serde(default)]
+    pub line_info: HashMap<u8, UciInfo>,
+    // latest `info` of the search, including the ones without a line
+    #[serde(default)]
+    pub search_info: Option<UciInfo>,
 }
 
 impl std::fmt::Display for PvObject {
@@ -63,12 +141,30 @@ impl std::fmt::Display for PvObject {
         let mut entries: Vec<(&u8, &PvLineData)> = self.lines.iter().collect();
         entries.sort_by_key(|(k, _)| *k);
         for (k, line) in entries {
-            writeln!(f, "  {}. {}", k, line)?;
+            match self.san_lines.get(k).filter(|san| !san.moves.is_empty()) {
+                Some(san) => {
+                    let eval = PvLineData {
+                        moves: san.text.clone(),
+                        eval_kind: line.eval_kind.clone(),
+                        eval_value: line.eval_value,
+                    };
+                    writeln!(f, "  {}. {}", k, eval)?;
+                }
+                None => writeln!(f, "  {}. {}", k, line)?,
+            }
         }
         Ok(())
     }
 }
 impl PvObject {
+    /// Write line `multipv` in SAN, `board` being the position searched.
+    pub fn update_san_line(&mut self, board: &Board, multipv: u8) {
+        if let Some(line) = self.lines.get(&multipv) {
+            let san = SanLine::from_uci(board, &line.moves);
+            self.san_lines.insert(multipv, san);
+        }
+    }
+
     /// Return the first move (as a string) of the highest-rated PV line, if any.
     /// The "highest-rated" line is chosen by a simple numeric score:
     /// - Centipawn evaluations use their raw value.
@@ -120,6 +216,9 @@ impl Default for PvObject {
             fen: String::new(),
             depth: 0,
             lines: std::collections::HashMap::new(),
+            san_lines: std::collections::HashMap::new(),
+            line_info: std::collections::HashMap::new(),
+            search_info: None,
         }
     }
 }
@@ -170,7 +269,7 @@ pub struct OpeningEntry<'a> {
 }
 
 pub struct ServerState<'a> {
-    pub engine: Option<Stockfish>,
+    pub engine: Option<UciEngine>,
     pub opening_index: Option<HashMap<String, OpeningEntry<'a>>>,
     pub game_controller: GameController,
     pub analyzer_controller: AnalyzerController,
@@ -179,6 +278,15 @@ pub struct ServerState<'a> {
     pub total_memory: f64,
     pub nbcpu: usize,
     pub settings: Settings,
+    // flag of the running PGN import, replaced on every new import
+    pub import_cancel: Arc<AtomicBool>,
+    // flag of the running game analysis, replaced on every new analysis
+    pub analysis_cancel: Arc<AtomicBool>,
+    // last game edit or delete, revertible for a short time
+    pub game_undo: Option<GameUndo>,
+    pub database: Database,
+    // why the database file couldn't be opened; games then only live in memory
+    pub database_error: Option<String>,
 }
 impl<'a> Default for ServerState<'a> {
     fn default() -> Self {
@@ -186,23 +294,30 @@ impl<'a> Default for ServerState<'a> {
             corrupted: true,
             map: HashMap::new(),
         });
-        let stockfish_elo = settings
-            .map
-            .get("StockfishElo")
-            .unwrap_or(&"600".to_string())
-            .parse()
-            .unwrap_or(600);
-
-        let engine = match Stockfish::new("/usr/bin/stockfish") {
-            Ok(mut s) => {
+        let (database, database_error) = match Database::from_settings(&settings) {
+            Ok(database) => (database, None),
+            Err(e) => {
+                eprintln!("[DB] could not open the database, keeping games in memory: {e}");
+                (Database::in_memory().expect("in-memory database"), Some(e.to_string()))
+            }
+        };
+        let engine_config = database
+            .connection()
+            .map(|con| engine_for(&con, &settings, EngineRole::Game))
+            .unwrap_or_else(|_| EngineConfig::default_engine());
+        let engine = match engine_config.launch() {
+            Ok((mut s, _)) => {
                 if s.setup_for_new_game().is_ok() {
-                    s.set_elo(stockfish_elo);
+                    s.set_elo(DEFAULT_RATING).ok();
                     Some(s)
                 } else {
                     None
                 }
             }
-            Err(_) => None,
+            Err(e) => {
+                eprintln!("[Engine] {e}");
+                None
+            }
         };
         let game_controller = GameController::default();
         let analyzer_controller = AnalyzerController::default();
@@ -233,9 +348,6 @@ impl<'a> Default for ServerState<'a> {
             None => {}
         }
 
-        database::create::create_database()
-            .inspect_err(|e| eprintln!("{e}"))
-            .ok();
         return ServerState {
             engine,
             game_controller,
@@ -246,25 +358,70 @@ impl<'a> Default for ServerState<'a> {
             total_memory: 0.0,
             nbcpu: 1,
             settings: settings,
+            import_cancel: Arc::new(AtomicBool::new(false)),
+            analysis_cancel: Arc::new(AtomicBool::new(false)),
+            game_undo: None,
+            database,
+            database_error,
         };
     }
 }
 impl<'a> ServerState<'a> {
-    pub fn update_elo(&mut self, elo_delta: i32) {
-        let string_elo = self.settings.map.get("PlayerElo");
-        match string_elo {
-            Some(elo) => {
-                let num_elo = elo.parse::<i32>().unwrap();
-                let updated_elo = num_elo + elo_delta;
-                self.settings
-                    .update("PlayerElo".to_string(), updated_elo.to_string());
-                self.settings
-                    .update("StockfishElo".to_string(), (updated_elo + 50).to_string());
-            }
-            None => {
-                println!("[SETTINGS] No player elo in settings")
-            }
+    /// Seat the player's rating in the current time class and the engine's in
+    /// the game controller. Once rated, the engine plays a bit above the player.
+    /// A class without games starts at the rating of the old `PlayerElo`
+    /// setting when there is one.
+    pub fn apply_ratings(&mut self) {
+        let mode = self.game_controller.mode;
+        let legacy: Option<u32> = self
+            .settings
+            .map
+            .get(LEGACY_RATING_SETTING)
+            .and_then(|value| value.trim().parse().ok());
+        let stored = self
+            .database
+            .connection()
+            .and_then(|con| match (current_rating(&con, mode)?, legacy) {
+                (None, Some(rating)) => {
+                    seed_rating(&con, mode, rating)?;
+                    Ok(Some((rating, 0)))
+                }
+                (stored, _) => Ok(stored),
+            })
+            .inspect_err(|e| eprintln!("[DB] could not read the rating: {e}"))
+            .ok()
+            .flatten();
+        let (player, engine, rated_games) = match stored {
+            Some((rating, games)) => (rating, rating + ENGINE_RATING_OFFSET, games),
+            None => (DEFAULT_RATING, DEFAULT_RATING, 0),
         };
+        self.game_controller
+            .set_ratings(player, engine, rated_games);
+        if let Some(stockfish) = self.engine.as_mut() {
+            stockfish.set_elo(engine).ok();
+            stockfish.ensure_ready().ok();
+        }
+    }
+
+    /// Store the rating change of the finished game in `rating_history`, once.
+    pub fn record_game_rating(&mut self) {
+        let controller = &self.game_controller;
+        let (Some(gain), None) = (controller.elo_gain, controller.rating_entry) else {
+            return;
+        };
+        let Some(score) = controller.player_score() else {
+            return;
+        };
+        let (before, opponent) = controller.player_rating();
+        let after = (before as i32 + gain).max(0) as u32;
+        let recorded = self
+            .database
+            .connection()
+            .and_then(|con| record_rating(&con, controller.mode, before, after, opponent, score));
+        match recorded {
+            Ok(entry_id) => self.game_controller.rating_entry = Some(entry_id),
+            Err(e) => eprintln!("[DB] could not record the rating: {e}"),
+        }
     }
 }
 #[tauri::command]
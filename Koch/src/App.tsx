@@ -1,4 +1,5 @@
-import { SetStateAction, useState } from "react";
+import { SetStateAction, useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
 
 import "./App.css";
 import { Sidebar } from "./components/Sidebar";
@@ -12,6 +13,14 @@ function App() {
 
   const [centerSection, setCenterSection] = useState<AppScreen>("Home");
   const [selectedGameId, setSelectedGameId] = useState<number | null>(null);
+  // set when the games database couldn't be opened and games only live in memory
+  const [databaseError, setDatabaseError] = useState<string | null>(null);
+
+  useEffect(() => {
+    invoke<string | null>("get_database_error")
+      .then(setDatabaseError)
+      .catch((e) => console.error("get_database_error failed", e));
+  }, []);
 
   function changeSection(screen: AppScreen) {
     setCenterSection(screen);
@@ -24,6 +33,13 @@ function App() {
 
   return (
     <main className="bg-background-dark">
+      {databaseError && (
+        <div className="absolute top-1 left-[25%] w-[50%] z-50 p-3 rounded bg-red-800 text-white">
+          <p>The games database could not be opened: {databaseError}</p>
+          <p>Games of this session are kept in memory and lost when Koch closes. Pick another database file in the settings.</p>
+          <button className="mt-2 underline" onClick={() => setDatabaseError(null)}>Dismiss</button>
+        </div>
+      )}
       <div className="flex flex-row w-[100vw] h-[100vh] ">
 
         <Sidebar handleClick={changeSection} selectedScreen={centerSection} />
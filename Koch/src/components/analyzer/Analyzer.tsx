@@ -9,6 +9,7 @@ import { PieceColor } from "../../../src-tauri/bindings/PieceColor";
 import { AnalyzerController } from "../../../src-tauri/bindings/AnalyzerController";
 import type { PvObject } from "../../../src-tauri/bindings/PvObject";
 import type { PvLineData } from "../../../src-tauri/bindings/PvLineData";
+import type { Threat } from "../../../src-tauri/bindings/Threat";
 
 import { EvalType } from "../../../src-tauri/bindings/EvalType";
 import { ArrowData } from "../ArrowLayer";
@@ -531,9 +532,9 @@ export function Analyzer({ gameId, setSelectedGameId }: AnalyzerProps) {
         let unlisten: (() => void) | undefined;
         (async () => {
             try {
-                unlisten = await listen<string>("threat_update", (e) => {
+                unlisten = await listen<Threat>("threat_update", (e) => {
                     //console.log("threat_update:", e.payload);
-                    updateThreat(e.payload);
+                    updateThreat(e.payload.line?.moves ?? null);
                 });
             } catch (err) { console.error("threat_update listen failed", err); }
         })();